@@ -1,21 +1,83 @@
 use crate::data::{
-    KeyValueStoreGetResponse, KeyValueStoreMethod, KeyValueStoreSetResponse,
-    ScheduleCreateResponse, ScheduleJobWithOptionalId, ScheduleListResponse, ScheduleMethod,
-    ScheduleUpdateResponse, SysGetConfigResponse, SysGetStatusResponse, SysMethod,
+    CctMethod, DeviceInfo, Gen1RelaySettings, Gen1RelayStatus, Gen1Settings, Gen1ShellyInfo,
+    Gen1Status, KeyValueStoreDeleteResponse, KeyValueStoreGetResponse, KeyValueStoreMethod,
+    KeyValueStoreSetResponse, LightGetStatusResponse, LightGetStatusResponseResult, LightMethod,
+    LightSetResponse, RgbwMethod, ScheduleCreateResponse, ScheduleJobWithOptionalId,
+    ScheduleListResponse, ScheduleMethod, ScheduleUpdateResponse, ScriptCreateResponse, ScriptInfo,
+    ScriptListResponse, ScriptMethod, ScriptPutCodeResponse, ScriptResponse,
+    ScriptStartStopResponse, ShellyGetComponentsResponse, ShellyGetDeviceInfoResponse,
+    ShellyListMethodsResponse, ShellyMethod, SwitchGetStatusResponse,
+    SwitchGetStatusResponseResult, SwitchMethod, SwitchSetResponse, SysGetConfigResponse,
+    SysGetStatusResponse, SysMethod,
 };
 use crate::error::ShellyRpcError;
+use crate::mqtt::MqttRpc;
+use crate::outbound::OutboundServer;
 use chrono::{NaiveTime, Utc};
 use log::trace;
+use reqwest::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
 use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+
+/// User name of devices with authentication enabled, fixed on Gen2 devices and the
+/// default on Gen1 devices.
+const AUTH_USER: &str = "admin";
+
+/// Channel carrying the RPC calls of a [`Gen2DeviceClient`].
+#[derive(Debug, Clone, Copy)]
+enum Transport<'a> {
+    /// HTTP POST to `http://<address>/rpc`.
+    Http,
+    /// Publish to `<address>/rpc` through the broker of the session.
+    Mqtt(&'a MqttRpc),
+    /// Send over the outbound websocket the device `<address>` opened to the server.
+    Outbound(&'a OutboundServer),
+}
 
 #[derive(Debug)]
 pub struct Gen2DeviceClient<'a> {
     address: &'a str,
+    password: Option<&'a str>,
+    transport: Transport<'a>,
 }
 
 impl<'a> Gen2DeviceClient<'a> {
     pub fn new(address: &'a str) -> Self {
-        Self { address }
+        Self {
+            address,
+            password: None,
+            transport: Transport::Http,
+        }
+    }
+
+    /// Sends the calls through an MQTT broker instead of HTTP, for devices that are only
+    /// reachable through the broker. The address is then the device's MQTT topic prefix,
+    /// by default its device id, e.g. `shellyplus1pm-a8032abe54dc`. Keeps the transport
+    /// if `mqtt` is `None`.
+    pub fn with_mqtt(mut self, mqtt: Option<&'a MqttRpc>) -> Self {
+        if let Some(mqtt) = mqtt {
+            self.transport = Transport::Mqtt(mqtt);
+        }
+        self
+    }
+
+    /// Sends the calls over the outbound websocket the device opened to `server`, for
+    /// devices behind NAT. The address is then the device id, e.g.
+    /// `shellyplus1pm-a8032abe54dc`. Keeps the transport if `server` is `None`.
+    pub fn with_outbound(mut self, server: Option<&'a OutboundServer>) -> Self {
+        if let Some(server) = server {
+            self.transport = Transport::Outbound(server);
+        }
+        self
+    }
+
+    /// Sets the password of a device with authentication enabled, sent with
+    /// HTTP digest authentication when the device asks for it.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/General/Authentication
+    pub fn with_password(mut self, password: Option<&'a str>) -> Self {
+        self.password = password;
+        self
     }
 
     /// https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Schedule#schedulecreate
@@ -24,8 +86,7 @@ impl<'a> Gen2DeviceClient<'a> {
         job: &ScheduleJobWithOptionalId,
     ) -> Result<ScheduleCreateResponse, ShellyRpcError> {
         trace!("create_schedule");
-        Self::execute_rpc(
-            self.address,
+        self.execute_rpc(
             &serde_json::json!({"id": 1, "method": ScheduleMethod::Create, "params": job}),
         )
         .await
@@ -37,8 +98,7 @@ impl<'a> Gen2DeviceClient<'a> {
         job: &ScheduleJobWithOptionalId,
     ) -> Result<ScheduleUpdateResponse, ShellyRpcError> {
         trace!("update_schedule");
-        Self::execute_rpc(
-            self.address,
+        self.execute_rpc(
             &serde_json::json!({"id": 1, "method": ScheduleMethod::Update, "params": job}),
         )
         .await
@@ -49,8 +109,7 @@ impl<'a> Gen2DeviceClient<'a> {
         job_id: u32,
     ) -> Result<ScheduleUpdateResponse, ShellyRpcError> {
         trace!("disable_schedule");
-        Self::execute_rpc(
-            self.address,
+        self.execute_rpc(
             &serde_json::json!({"id": 1, "method": ScheduleMethod::Update, "params": { "id": job_id, "enable": false } }),
         )
         .await
@@ -59,11 +118,90 @@ impl<'a> Gen2DeviceClient<'a> {
     /// https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Schedule#schedulelist
     pub async fn list_schedule(&self) -> Result<ScheduleListResponse, ShellyRpcError> {
         trace!("list_schedule");
-        Self::execute_rpc(
-            self.address,
-            &serde_json::json!({"id": 1, "method": ScheduleMethod::List}),
-        )
-        .await
+        self.execute_rpc(&serde_json::json!({"id": 1, "method": ScheduleMethod::List}))
+            .await
+    }
+
+    /// Creates an empty, stopped script with the given name and returns its id.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Script#scriptcreate
+    pub async fn create_script(&self, name: &str) -> Result<u32, ShellyRpcError> {
+        trace!("create_script '{name}'");
+        let resp: ScriptCreateResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ScriptMethod::Create, "params": { "name": name }}))
+            .await?;
+        Ok(resp.result.id)
+    }
+
+    /// Replaces the code of the script with the given id, or appends to it, and returns
+    /// the length of the code. The device limits the size of a single call, so long code
+    /// is uploaded in chunks.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Script#scriptputcode
+    pub async fn put_script_code(
+        &self,
+        id: u32,
+        code: &str,
+        append: bool,
+    ) -> Result<u32, ShellyRpcError> {
+        trace!(
+            "put_script_code {id}: {} bytes, append: {append}",
+            code.len()
+        );
+        let resp: ScriptPutCodeResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ScriptMethod::PutCode, "params": { "id": id, "code": code, "append": append }}))
+            .await?;
+        Ok(resp.result.len)
+    }
+
+    /// Sets whether the script with the given id is started when the device boots.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Script#scriptsetconfig
+    pub async fn set_script_config(&self, id: u32, enable: bool) -> Result<(), ShellyRpcError> {
+        trace!("set_script_config {id}: enable: {enable}");
+        let _: ScriptResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ScriptMethod::SetConfig, "params": { "id": id, "config": { "enable": enable } }}))
+            .await?;
+        Ok(())
+    }
+
+    /// Starts the script with the given id and returns whether it was running before.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Script#scriptstart
+    pub async fn start_script(&self, id: u32) -> Result<bool, ShellyRpcError> {
+        trace!("start_script {id}");
+        let resp: ScriptStartStopResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ScriptMethod::Start, "params": { "id": id }}))
+            .await?;
+        Ok(resp.result.was_running)
+    }
+
+    /// Stops the script with the given id and returns whether it was running before.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Script#scriptstop
+    pub async fn stop_script(&self, id: u32) -> Result<bool, ShellyRpcError> {
+        trace!("stop_script {id}");
+        let resp: ScriptStartStopResponse = self
+            .execute_rpc(
+                &serde_json::json!({"id": 1, "method": ScriptMethod::Stop, "params": { "id": id }}),
+            )
+            .await?;
+        Ok(resp.result.was_running)
+    }
+
+    /// Deletes the stopped script with the given id.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Script#scriptdelete
+    pub async fn delete_script(&self, id: u32) -> Result<(), ShellyRpcError> {
+        trace!("delete_script {id}");
+        let _: ScriptResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ScriptMethod::Delete, "params": { "id": id }}))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the scripts installed on this [`Gen2DeviceClient`].
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Script#scriptlist
+    pub async fn list_scripts(&self) -> Result<Vec<ScriptInfo>, ShellyRpcError> {
+        trace!("list_scripts");
+        let resp: ScriptListResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ScriptMethod::List}))
+            .await?;
+        Ok(resp.result.scripts)
     }
 
     /// Returns the get time of this [`Gen2DeviceClient`].
@@ -71,11 +209,9 @@ impl<'a> Gen2DeviceClient<'a> {
     /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Sys#sysgetstatus
     pub async fn get_time(&self) -> Result<i64, ShellyRpcError> {
         trace!("get_time");
-        let resp: SysGetStatusResponse = Self::execute_rpc(
-            self.address,
-            &serde_json::json!({"id": 1, "method": SysMethod::GetStatus}),
-        )
-        .await?;
+        let resp: SysGetStatusResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": SysMethod::GetStatus}))
+            .await?;
 
         if let Some(unix_timestamp) = resp.result.unixtime {
             Ok(unix_timestamp)
@@ -90,16 +226,53 @@ impl<'a> Gen2DeviceClient<'a> {
         }
     }
 
+    /// Returns the identification of this [`Gen2DeviceClient`], e.g. model, generation
+    /// and firmware.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Shelly#shellygetdeviceinfo
+    pub async fn get_device_info(&self) -> Result<DeviceInfo, ShellyRpcError> {
+        trace!("get_device_info");
+        let resp: ShellyGetDeviceInfoResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ShellyMethod::GetDeviceInfo}))
+            .await?;
+        Ok(resp.result)
+    }
+
+    /// Returns the RPC methods of this [`Gen2DeviceClient`], e.g. `Schedule.Create`.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Shelly#shellylistmethods
+    pub async fn list_methods(&self) -> Result<Vec<String>, ShellyRpcError> {
+        trace!("list_methods");
+        let resp: ShellyListMethodsResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": ShellyMethod::ListMethods}))
+            .await?;
+        Ok(resp.result.methods)
+    }
+
+    /// Returns the keys of the components of this [`Gen2DeviceClient`], e.g. `switch:0`.
+    /// Fetches all pages of the list.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Shelly#shellygetcomponents
+    pub async fn get_components(&self) -> Result<Vec<String>, ShellyRpcError> {
+        trace!("get_components");
+        let mut keys = Vec::new();
+        loop {
+            let resp: ShellyGetComponentsResponse = self
+                .execute_rpc(&serde_json::json!({"id": 1, "method": ShellyMethod::GetComponents, "params": { "offset": keys.len() }}))
+                .await?;
+            let page = resp.result.components.len();
+            keys.extend(resp.result.components.into_iter().map(|c| c.key));
+            if page == 0 || keys.len() >= resp.result.total as usize {
+                return Ok(keys);
+            }
+        }
+    }
+
     /// Returns the get location of this [`Gen2DeviceClient`].
     /// Calls the Sys.GetConfig endpoint to retrieve the location.
     /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Sys#sysgetconfig
     pub async fn get_location(&self) -> Result<(f64, f64), ShellyRpcError> {
         trace!("get_location");
-        let resp: SysGetConfigResponse = Self::execute_rpc(
-            self.address,
-            &serde_json::json!({"id": 1, "method": SysMethod::GetConfig}),
-        )
-        .await?;
+        let resp: SysGetConfigResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": SysMethod::GetConfig}))
+            .await?;
         Ok((resp.result.location.lat, resp.result.location.lon))
     }
 
@@ -108,8 +281,7 @@ impl<'a> Gen2DeviceClient<'a> {
     /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/KVS#kvsget
     pub async fn get_value(&self, key: &str) -> Result<String, ShellyRpcError> {
         trace!("get_value '{key}'");
-        let resp: KeyValueStoreGetResponse = Self::execute_rpc(
-            self.address,
+        let resp: KeyValueStoreGetResponse = self.execute_rpc(
             &serde_json::json!({"id": 1, "method": KeyValueStoreMethod::Get, "params": { "key": key}}),
         )
         .await?;
@@ -122,8 +294,7 @@ impl<'a> Gen2DeviceClient<'a> {
     /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/KVS#kvsset
     pub async fn set_value(&self, key: &str, value: &str) -> Result<u32, ShellyRpcError> {
         trace!("set_value '{key}': '{value}'");
-        let resp: KeyValueStoreSetResponse = Self::execute_rpc(
-            self.address,
+        let resp: KeyValueStoreSetResponse = self.execute_rpc(
             &serde_json::json!({"id": 1, "method": KeyValueStoreMethod::Set, "params": { "key": key, "value": value}}),
         )
         .await?;
@@ -131,22 +302,211 @@ impl<'a> Gen2DeviceClient<'a> {
         Ok(resp.result.rev)
     }
 
-    async fn execute_rpc<T, R>(address: &str, body: &T) -> Result<R, ShellyRpcError>
+    /// Deletes the key and its value from the KVS of this [`Gen2DeviceClient`].
+    /// Calls the KVS.Delete endpoint to remove the key.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/KVS#kvsdelete
+    pub async fn delete_value(&self, key: &str) -> Result<u32, ShellyRpcError> {
+        trace!("delete_value '{key}'");
+        let resp: KeyValueStoreDeleteResponse = self.execute_rpc(
+            &serde_json::json!({"id": 1, "method": KeyValueStoreMethod::Delete, "params": { "key": key}}),
+        )
+        .await?;
+
+        Ok(resp.result.rev)
+    }
+
+    /// Returns the status of the switch with the given id of this [`Gen2DeviceClient`].
+    /// Calls the Switch.GetStatus endpoint to retrieve the output state.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Switch#switchgetstatus
+    pub async fn get_switch_status(
+        &self,
+        id: u8,
+    ) -> Result<SwitchGetStatusResponseResult, ShellyRpcError> {
+        trace!("get_switch_status {id}");
+        let resp: SwitchGetStatusResponse = self.execute_rpc(
+            &serde_json::json!({"id": 1, "method": SwitchMethod::GetStatus, "params": { "id": id }}),
+        )
+        .await?;
+
+        Ok(resp.result)
+    }
+
+    /// Sets the output of the switch with the given id of this [`Gen2DeviceClient`],
+    /// optionally flipping it back after `toggle_after` seconds.
+    /// Calls the Switch.Set endpoint and returns whether the output was on before.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Switch#switchset
+    pub async fn set_switch(
+        &self,
+        id: u8,
+        on: bool,
+        toggle_after: Option<i64>,
+    ) -> Result<bool, ShellyRpcError> {
+        trace!("set_switch {id}: {on}, toggle_after: {toggle_after:?}");
+        let mut params = serde_json::json!({ "id": id, "on": on });
+        if let Some(toggle_after) = toggle_after {
+            params["toggle_after"] = toggle_after.into();
+        }
+        let resp: SwitchSetResponse = self
+            .execute_rpc(
+                &serde_json::json!({"id": 1, "method": SwitchMethod::Set, "params": params}),
+            )
+            .await?;
+
+        Ok(resp.result.was_on)
+    }
+
+    /// Returns the status of the light with the given id of this [`Gen2DeviceClient`].
+    /// Calls the Light.GetStatus endpoint to retrieve the output state and brightness.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Light#lightgetstatus
+    pub async fn get_light_status(
+        &self,
+        id: u8,
+    ) -> Result<LightGetStatusResponseResult, ShellyRpcError> {
+        trace!("get_light_status {id}");
+        let resp: LightGetStatusResponse = self.execute_rpc(
+            &serde_json::json!({"id": 1, "method": LightMethod::GetStatus, "params": { "id": id }}),
+        )
+        .await?;
+
+        Ok(resp.result)
+    }
+
+    /// Sets the output and optionally the brightness in percent of the light with the given
+    /// id of this [`Gen2DeviceClient`], optionally flipping it back after `toggle_after` seconds.
+    /// Calls the Light.Set endpoint.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Light#lightset
+    pub async fn set_light(
+        &self,
+        id: u8,
+        on: bool,
+        brightness: Option<u8>,
+        toggle_after: Option<i64>,
+    ) -> Result<(), ShellyRpcError> {
+        trace!("set_light {id}: {on}, brightness: {brightness:?}, toggle_after: {toggle_after:?}");
+        let mut params = serde_json::json!({ "id": id, "on": on });
+        if let Some(brightness) = brightness {
+            params["brightness"] = brightness.into();
+        }
+        if let Some(toggle_after) = toggle_after {
+            params["toggle_after"] = toggle_after.into();
+        }
+        let _: LightSetResponse = self
+            .execute_rpc(
+                &serde_json::json!({"id": 1, "method": LightMethod::Set, "params": params}),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the status of the CCT light with the given id of this [`Gen2DeviceClient`].
+    /// Calls the CCT.GetStatus endpoint to retrieve the output state and brightness.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/CCT#cctgetstatus
+    pub async fn get_cct_status(
+        &self,
+        id: u8,
+    ) -> Result<LightGetStatusResponseResult, ShellyRpcError> {
+        trace!("get_cct_status {id}");
+        let resp: LightGetStatusResponse = self.execute_rpc(
+            &serde_json::json!({"id": 1, "method": CctMethod::GetStatus, "params": { "id": id }}),
+        )
+        .await?;
+
+        Ok(resp.result)
+    }
+
+    /// Sets the output, brightness in percent and colour temperature `ct` in kelvin of the
+    /// CCT light with the given id of this [`Gen2DeviceClient`], optionally flipping it back
+    /// after `toggle_after` seconds.
+    /// Calls the CCT.Set endpoint.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/CCT#cctset
+    pub async fn set_cct(
+        &self,
+        id: u8,
+        on: bool,
+        brightness: Option<u8>,
+        ct: Option<u16>,
+        toggle_after: Option<i64>,
+    ) -> Result<(), ShellyRpcError> {
+        trace!("set_cct {id}: {on}, brightness: {brightness:?}, ct: {ct:?}, toggle_after: {toggle_after:?}");
+        let mut params = serde_json::json!({ "id": id, "on": on });
+        if let Some(brightness) = brightness {
+            params["brightness"] = brightness.into();
+        }
+        if let Some(ct) = ct {
+            params["ct"] = ct.into();
+        }
+        if let Some(toggle_after) = toggle_after {
+            params["toggle_after"] = toggle_after.into();
+        }
+        let _: LightSetResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": CctMethod::Set, "params": params}))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the status of the RGBW light with the given id of this [`Gen2DeviceClient`].
+    /// Calls the RGBW.GetStatus endpoint to retrieve the output state and brightness.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/RGBW#rgbwgetstatus
+    pub async fn get_rgbw_status(
+        &self,
+        id: u8,
+    ) -> Result<LightGetStatusResponseResult, ShellyRpcError> {
+        trace!("get_rgbw_status {id}");
+        let resp: LightGetStatusResponse = self.execute_rpc(
+            &serde_json::json!({"id": 1, "method": RgbwMethod::GetStatus, "params": { "id": id }}),
+        )
+        .await?;
+
+        Ok(resp.result)
+    }
+
+    /// Sets the output, brightness in percent and colour `rgb` of the RGBW light with the
+    /// given id of this [`Gen2DeviceClient`], optionally flipping it back after `toggle_after`
+    /// seconds.
+    /// Calls the RGBW.Set endpoint.
+    /// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/RGBW#rgbwset
+    pub async fn set_rgbw(
+        &self,
+        id: u8,
+        on: bool,
+        brightness: Option<u8>,
+        rgb: Option<[u8; 3]>,
+        toggle_after: Option<i64>,
+    ) -> Result<(), ShellyRpcError> {
+        trace!("set_rgbw {id}: {on}, brightness: {brightness:?}, rgb: {rgb:?}, toggle_after: {toggle_after:?}");
+        let mut params = serde_json::json!({ "id": id, "on": on });
+        if let Some(brightness) = brightness {
+            params["brightness"] = brightness.into();
+        }
+        if let Some(rgb) = rgb {
+            params["rgb"] = serde_json::json!(rgb);
+        }
+        if let Some(toggle_after) = toggle_after {
+            params["toggle_after"] = toggle_after.into();
+        }
+        let _: LightSetResponse = self
+            .execute_rpc(&serde_json::json!({"id": 1, "method": RgbwMethod::Set, "params": params}))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn execute_rpc<T, R>(&self, body: &T) -> Result<R, ShellyRpcError>
     where
         T: Serialize,
         R: DeserializeOwned,
     {
         trace!(
-            "execute_rpc<T, R>(address: {address}, body: {:?})",
+            "execute_rpc<T, R>(address: {}, body: {:?})",
+            self.address,
             serde_json::to_string(body)
         );
-        let res_body = reqwest::Client::new()
-            .post(format!("http://{address}/rpc"))
-            .json(body)
-            .send()
-            .await?
-            .text()
-            .await?;
+        let res_body = match self.transport {
+            Transport::Http => self.execute_http(body).await?,
+            Transport::Mqtt(_) | Transport::Outbound(_) => self.execute_frame(body).await?,
+        };
 
         match serde_json::from_str(&res_body) {
             Ok(r) => Ok(r),
@@ -156,4 +516,271 @@ impl<'a> Gen2DeviceClient<'a> {
             },
         }
     }
+
+    async fn execute_http<T: Serialize>(&self, body: &T) -> Result<String, ShellyRpcError> {
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/rpc", self.address);
+        let mut res = client.post(&url).json(body).send().await?;
+
+        if let (StatusCode::UNAUTHORIZED, Some(password)) = (res.status(), self.password) {
+            let challenge = res
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(DigestChallenge::parse)
+                .ok_or(ShellyRpcError::AuthError)?;
+            res = client
+                .post(&url)
+                .header(AUTHORIZATION, challenge.authorization(password, "/rpc"))
+                .json(body)
+                .send()
+                .await?;
+        }
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(ShellyRpcError::AuthError);
+        }
+        Ok(res.text().await?)
+    }
+
+    /// Sends the call as an RPC frame over MQTT or a websocket. Without HTTP, a device
+    /// with authentication enabled replies with error 401 carrying the digest challenge,
+    /// which is answered in the `auth` object of the repeated call.
+    async fn execute_frame<T: Serialize>(&self, body: &T) -> Result<String, ShellyRpcError> {
+        let mut frame = serde_json::to_value(body)?;
+        let mut reply = self.call_frame(&frame).await?;
+
+        if let (Some(challenge), Some(password)) =
+            (DigestChallenge::parse_rpc_error(&reply), self.password)
+        {
+            frame["auth"] = challenge.auth(password);
+            reply = self.call_frame(&frame).await?;
+        }
+        if DigestChallenge::parse_rpc_error(&reply).is_some() {
+            return Err(ShellyRpcError::AuthError);
+        }
+        Ok(reply)
+    }
+
+    async fn call_frame(&self, frame: &serde_json::Value) -> Result<String, ShellyRpcError> {
+        match self.transport {
+            Transport::Http => unreachable!("HTTP calls are not sent as frames"),
+            Transport::Mqtt(mqtt) => mqtt.call(self.address, frame).await,
+            Transport::Outbound(server) => server.call(self.address, frame).await,
+        }
+    }
+}
+
+/// Client of the HTTP API of Gen1 devices, e.g. Shelly 1 and Shelly 1PM.
+/// See: https://shelly-api-docs.shelly.cloud/gen1/
+#[derive(Debug)]
+pub struct Gen1DeviceClient<'a> {
+    address: &'a str,
+    password: Option<&'a str>,
+}
+
+impl<'a> Gen1DeviceClient<'a> {
+    pub fn new(address: &'a str) -> Self {
+        Self {
+            address,
+            password: None,
+        }
+    }
+
+    /// Sets the password of a device with restricted login, sent with HTTP basic
+    /// authentication.
+    pub fn with_password(mut self, password: Option<&'a str>) -> Self {
+        self.password = password;
+        self
+    }
+
+    /// Returns the identification of the device, answered by Gen1 and Gen2 devices alike.
+    /// See: https://shelly-api-docs.shelly.cloud/gen1/#shelly
+    pub async fn get_shelly(&self) -> Result<Gen1ShellyInfo, ShellyRpcError> {
+        trace!("get_shelly");
+        self.execute_get("/shelly", &[]).await
+    }
+
+    /// Returns the location of this [`Gen1DeviceClient`] from its settings.
+    /// See: https://shelly-api-docs.shelly.cloud/gen1/#settings
+    pub async fn get_location(&self) -> Result<(f64, f64), ShellyRpcError> {
+        trace!("get_location");
+        let settings: Gen1Settings = self.execute_get("/settings", &[]).await?;
+        Ok((settings.lat, settings.lng))
+    }
+
+    /// Returns the time of this [`Gen1DeviceClient`] from its status.
+    /// See: https://shelly-api-docs.shelly.cloud/gen1/#status
+    pub async fn get_time(&self) -> Result<i64, ShellyRpcError> {
+        trace!("get_time");
+        let status: Gen1Status = self.execute_get("/status", &[]).await?;
+        Ok(status.unixtime)
+    }
+
+    /// Returns the status of the relay with the given id.
+    /// See: https://shelly-api-docs.shelly.cloud/gen1/#shelly1-shelly1pm-relay-0
+    pub async fn get_relay_status(&self, id: u8) -> Result<Gen1RelayStatus, ShellyRpcError> {
+        trace!("get_relay_status {id}");
+        self.execute_get(&format!("/relay/{id}"), &[]).await
+    }
+
+    /// Switches the relay with the given id, optionally flipping it back after `timer`
+    /// seconds.
+    /// See: https://shelly-api-docs.shelly.cloud/gen1/#shelly1-shelly1pm-relay-0
+    pub async fn set_relay(
+        &self,
+        id: u8,
+        on: bool,
+        timer: Option<i64>,
+    ) -> Result<Gen1RelayStatus, ShellyRpcError> {
+        trace!("set_relay {id}: {on}, timer: {timer:?}");
+        let mut query = vec![("turn", if on { "on" } else { "off" }.to_string())];
+        if let Some(timer) = timer {
+            query.push(("timer", timer.to_string()));
+        }
+        self.execute_get(&format!("/relay/{id}"), &query).await
+    }
+
+    /// Returns the settings of the relay with the given id, including its schedule rules.
+    /// See: https://shelly-api-docs.shelly.cloud/gen1/#shelly1-shelly1pm-settings-relay-0
+    pub async fn get_relay_settings(&self, id: u8) -> Result<Gen1RelaySettings, ShellyRpcError> {
+        trace!("get_relay_settings {id}");
+        self.execute_get(&format!("/settings/relay/{id}"), &[])
+            .await
+    }
+
+    /// Replaces the schedule rules of the relay with the given id, e.g. `0600-0123456-on`.
+    /// The schedule is disabled if there are no rules.
+    /// See: https://shelly-api-docs.shelly.cloud/gen1/#shelly1-shelly1pm-settings-relay-0
+    pub async fn set_schedule_rules(
+        &self,
+        id: u8,
+        rules: &[String],
+    ) -> Result<Gen1RelaySettings, ShellyRpcError> {
+        trace!("set_schedule_rules {id}: {rules:?}");
+        let query = [
+            ("schedule", (!rules.is_empty()).to_string()),
+            ("schedule_rules", rules.join(",")),
+        ];
+        self.execute_get(&format!("/settings/relay/{id}"), &query)
+            .await
+    }
+
+    async fn execute_get<R>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<R, ShellyRpcError>
+    where
+        R: DeserializeOwned,
+    {
+        trace!(
+            "execute_get<R>(address: {}, path: {path}, query: {query:?})",
+            self.address
+        );
+        let url = format!("http://{}{path}", self.address);
+        let mut request = reqwest::Client::new().get(&url).query(query);
+        if let Some(password) = self.password {
+            request = request.basic_auth(AUTH_USER, Some(password));
+        }
+        let res = request.send().await?;
+
+        let status = res.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(ShellyRpcError::AuthError);
+        }
+        let res_body = res.text().await?;
+        if !status.is_success() {
+            return Err(ShellyRpcError::HttpStatusError(
+                status.as_u16(),
+                res_body.trim().to_string(),
+            ));
+        }
+        Ok(serde_json::from_str(&res_body)?)
+    }
+}
+
+/// The `WWW-Authenticate` challenge of a device with authentication enabled, e.g.
+/// `Digest qop="auth", realm="shellyplus1-a8032ab636ec", nonce="60dc59c6", algorithm=SHA-256`.
+#[derive(Debug, PartialEq)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+}
+
+impl DigestChallenge {
+    fn parse(header: &str) -> Option<Self> {
+        let params = header.strip_prefix("Digest ")?;
+        let param = |name: &str| {
+            params.split(',').find_map(|param| {
+                let (key, value) = param.trim().split_once('=')?;
+                (key == name).then(|| value.trim_matches('"').to_string())
+            })
+        };
+        Some(Self {
+            realm: param("realm")?,
+            nonce: param("nonce")?,
+        })
+    }
+
+    /// Parses the challenge of an RPC reply with error 401, whose message is e.g.
+    /// `{"auth_type": "digest", "nonce": 1625038776, "nc": 1, "realm": "shellypro4pm-f008d1d8b8b8", "algorithm": "SHA-256"}`.
+    fn parse_rpc_error(reply: &str) -> Option<Self> {
+        let reply: serde_json::Value = serde_json::from_str(reply).ok()?;
+        if reply["error"]["code"].as_i64()? != 401 {
+            return None;
+        }
+        let message: serde_json::Value =
+            serde_json::from_str(reply["error"]["message"].as_str()?).ok()?;
+        let nonce = match &message["nonce"] {
+            serde_json::Value::String(nonce) => nonce.clone(),
+            nonce => nonce.as_u64()?.to_string(),
+        };
+        Some(Self {
+            realm: message["realm"].as_str()?.to_string(),
+            nonce,
+        })
+    }
+
+    /// Returns the `auth` object answering the challenge in an RPC frame.
+    fn auth(&self, password: &str) -> serde_json::Value {
+        let cnonce = Utc::now().timestamp_subsec_nanos();
+        let ha1 = sha256_hex(&format!("{AUTH_USER}:{}:{password}", self.realm));
+        let ha2 = sha256_hex("dummy_method:dummy_uri");
+        let response = sha256_hex(&format!("{ha1}:{}:1:{cnonce}:auth:{ha2}", self.nonce));
+        let nonce = self
+            .nonce
+            .parse::<u64>()
+            .map_or_else(|_| self.nonce.clone().into(), serde_json::Value::from);
+        serde_json::json!({
+            "realm": self.realm,
+            "username": AUTH_USER,
+            "nonce": nonce,
+            "cnonce": cnonce,
+            "response": response,
+            "algorithm": "SHA-256",
+        })
+    }
+
+    /// Returns the `Authorization` header answering the challenge for a POST to `uri`.
+    fn authorization(&self, password: &str, uri: &str) -> String {
+        let cnonce = format!("{:x}", Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        let ha1 = sha256_hex(&format!("{AUTH_USER}:{}:{password}", self.realm));
+        let ha2 = sha256_hex(&format!("POST:{uri}"));
+        let response = sha256_hex(&format!(
+            "{ha1}:{}:00000001:{cnonce}:auth:{ha2}",
+            self.nonce
+        ));
+        format!(
+            "Digest username=\"{AUTH_USER}\", realm=\"{}\", nonce=\"{}\", uri=\"{uri}\", \
+             algorithm=SHA-256, response=\"{response}\", qop=auth, nc=00000001, cnonce=\"{cnonce}\"",
+            self.realm, self.nonce
+        )
+    }
+}
+
+fn sha256_hex(s: &str) -> String {
+    Sha256::digest(s.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
 }
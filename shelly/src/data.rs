@@ -81,6 +81,9 @@ pub enum KeyValueStoreMethod {
 
     #[serde(rename = "KVS.Get")]
     Get,
+
+    #[serde(rename = "KVS.Delete")]
+    Delete,
 }
 
 #[derive(Debug, Deserialize)]
@@ -97,6 +100,13 @@ pub struct KeyValueStoreSetResponse {
     pub result: KeyValueStoreSetResponseResult,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct KeyValueStoreDeleteResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: KeyValueStoreDeleteResponseResult,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct KeyValueStoreGetResponseResult {
     pub etag: String,
@@ -109,6 +119,177 @@ pub struct KeyValueStoreSetResponseResult {
     pub rev: u32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct KeyValueStoreDeleteResponseResult {
+    pub rev: u32,
+}
+
+//------------------------------
+// Script endpoint
+//------------------------------
+
+#[derive(Debug, Serialize)]
+pub enum ScriptMethod {
+    #[serde(rename = "Script.Create")]
+    Create,
+
+    #[serde(rename = "Script.PutCode")]
+    PutCode,
+
+    #[serde(rename = "Script.SetConfig")]
+    SetConfig,
+
+    #[serde(rename = "Script.Start")]
+    Start,
+
+    #[serde(rename = "Script.Stop")]
+    Stop,
+
+    #[serde(rename = "Script.Delete")]
+    Delete,
+
+    #[serde(rename = "Script.List")]
+    List,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptCreateResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: ScriptCreateResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptCreateResponseResult {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptPutCodeResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: ScriptPutCodeResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptPutCodeResponseResult {
+    /// Length of the script's code after the call.
+    pub len: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptStartStopResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: ScriptStartStopResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptStartStopResponseResult {
+    pub was_running: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptListResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: ScriptListResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptListResponseResult {
+    pub scripts: Vec<ScriptInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScriptInfo {
+    pub id: u32,
+    pub name: String,
+    /// Whether the script is started when the device boots.
+    pub enable: bool,
+    pub running: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScriptResponse {
+    pub id: u8,
+    pub src: String,
+    /// Script.SetConfig responds with `restart_required`, Script.Delete with `null`.
+    pub result: serde_json::Value,
+}
+
+//------------------------------
+// Shelly endpoint
+//------------------------------
+
+#[derive(Debug, Serialize)]
+pub enum ShellyMethod {
+    #[serde(rename = "Shelly.GetDeviceInfo")]
+    GetDeviceInfo,
+
+    #[serde(rename = "Shelly.ListMethods")]
+    ListMethods,
+
+    #[serde(rename = "Shelly.GetComponents")]
+    GetComponents,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShellyListMethodsResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: ShellyListMethodsResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShellyListMethodsResponseResult {
+    pub methods: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShellyGetComponentsResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: ShellyGetComponentsResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShellyGetComponentsResponseResult {
+    pub components: Vec<Component>,
+    pub offset: u32,
+    pub total: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Component {
+    /// Component key, e.g. `switch:0`.
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShellyGetDeviceInfoResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: DeviceInfo,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeviceInfo {
+    pub name: Option<String>,
+    pub id: String,
+    pub mac: String,
+    pub model: String,
+    #[serde(rename = "gen")]
+    pub generation: u8,
+    pub fw_id: String,
+    pub ver: String,
+    pub app: String,
+    #[serde(default)]
+    pub auth_en: bool,
+    /// Profile of multi-profile devices, e.g. `switch` or `cover` on a Plus 2PM.
+    pub profile: Option<String>,
+}
+
 //------------------------------
 // System endpoint
 //------------------------------
@@ -154,6 +335,172 @@ pub struct SysGetStatusResponseResult {
     pub unixtime: Option<i64>,
 }
 
+//------------------------------
+// Switch component
+//------------------------------
+
+#[derive(Debug, Serialize)]
+pub enum SwitchMethod {
+    #[serde(rename = "Switch.GetStatus")]
+    GetStatus,
+
+    #[serde(rename = "Switch.Set")]
+    Set,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchGetStatusResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: SwitchGetStatusResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchGetStatusResponseResult {
+    pub id: u8,
+    pub source: String,
+    pub output: bool,
+    pub timer_started_at: Option<f64>,
+    pub timer_duration: Option<f64>,
+    pub apower: Option<f64>,
+    pub current: Option<f64>,
+    pub aenergy: Option<EnergyCounter>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnergyCounter {
+    /// Total energy consumed in watt-hours since the last restart.
+    pub total: f64,
+    pub by_minute: Vec<f64>,
+    pub minute_ts: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchSetResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: SwitchSetResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SwitchSetResponseResult {
+    pub was_on: bool,
+}
+
+//------------------------------
+// Light component
+//------------------------------
+
+#[derive(Debug, Serialize)]
+pub enum LightMethod {
+    #[serde(rename = "Light.GetStatus")]
+    GetStatus,
+
+    #[serde(rename = "Light.Set")]
+    Set,
+}
+
+#[derive(Debug, Serialize)]
+pub enum CctMethod {
+    #[serde(rename = "CCT.GetStatus")]
+    GetStatus,
+
+    #[serde(rename = "CCT.Set")]
+    Set,
+}
+
+#[derive(Debug, Serialize)]
+pub enum RgbwMethod {
+    #[serde(rename = "RGBW.GetStatus")]
+    GetStatus,
+
+    #[serde(rename = "RGBW.Set")]
+    Set,
+}
+
+/// Status of a Light, CCT or RGBW component, which share these fields.
+#[derive(Debug, Deserialize)]
+pub struct LightGetStatusResponse {
+    pub id: u8,
+    pub src: String,
+    pub result: LightGetStatusResponseResult,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LightGetStatusResponseResult {
+    pub id: u8,
+    pub source: String,
+    pub output: bool,
+    /// Brightness in percent.
+    pub brightness: f64,
+    pub timer_started_at: Option<f64>,
+    pub timer_duration: Option<f64>,
+    pub apower: Option<f64>,
+    pub current: Option<f64>,
+    pub aenergy: Option<EnergyCounter>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LightSetResponse {
+    pub id: u8,
+    pub src: String,
+    /// Light.Set responds with a `null` result.
+    pub result: serde_json::Value,
+}
+
+//------------------------------
+// Gen1 HTTP API
+//------------------------------
+
+/// Identification of a device from `GET /shelly`, answered by both generations.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Gen1ShellyInfo {
+    /// Model of a Gen1 device, e.g. `SHSW-1`. Gen2 devices answer with `model` instead.
+    #[serde(rename = "type")]
+    pub model: Option<String>,
+    pub mac: String,
+    #[serde(default)]
+    pub auth: bool,
+    /// Firmware of a Gen1 device, e.g. `20230913-112003/v1.14.0-gcb84623`.
+    pub fw: Option<String>,
+    /// Generation of Gen2 devices, missing on Gen1 devices.
+    #[serde(rename = "gen")]
+    pub generation: Option<u8>,
+}
+
+impl Gen1ShellyInfo {
+    pub fn generation(&self) -> u8 {
+        self.generation.unwrap_or(1)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Gen1Settings {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Gen1Status {
+    pub unixtime: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Gen1RelayStatus {
+    pub ison: bool,
+    pub has_timer: bool,
+    #[serde(default)]
+    pub timer_remaining: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Gen1RelaySettings {
+    pub name: Option<String>,
+    pub schedule: bool,
+    /// Rules of the relay's schedule, e.g. `0600-0123456-on`.
+    pub schedule_rules: Vec<String>,
+}
+
 //------------------------------
 // Generic error type
 //------------------------------
@@ -7,6 +7,18 @@ pub enum ShellyRpcError {
     SerdeJsonError(serde_json::Error),
     SerdeJsonBiError(serde_json::Error, serde_json::Error),
     HttpApiError(ShellyError),
+    /// A Gen1 device answered with an error status, e.g. `400 Bad Request`.
+    HttpStatusError(u16, String),
+    AuthError,
+    MqttClientError(rumqttc::ClientError),
+    MqttConnectionError(rumqttc::ConnectionError),
+    /// The broker address is not `host` or `host:port`.
+    MqttBrokerError(String),
+    /// The device with the given address did not reply in time.
+    NoReply(String),
+    /// The device with the given id has no outbound websocket open.
+    NotConnected(String),
+    IoError(std::io::Error),
 }
 
 impl Error for ShellyRpcError {}
@@ -27,6 +39,31 @@ impl std::fmt::Display for ShellyRpcError {
                 "Shelly API error: (code: {}, message: {})",
                 e.error.code, e.error.message
             ),
+            ShellyRpcError::HttpStatusError(status, message) => {
+                write!(
+                    f,
+                    "Shelly HTTP error: (status: {}, message: {})",
+                    status, message
+                )
+            }
+            ShellyRpcError::AuthError => {
+                write!(
+                    f,
+                    "Authentication failed: the device requires a valid password"
+                )
+            }
+            ShellyRpcError::MqttClientError(e) => write!(f, "MQTT client error: {}", e),
+            ShellyRpcError::MqttConnectionError(e) => write!(f, "MQTT connection error: {}", e),
+            ShellyRpcError::MqttBrokerError(broker) => {
+                write!(
+                    f,
+                    "Invalid MQTT broker '{}', expected host or host:port",
+                    broker
+                )
+            }
+            ShellyRpcError::NoReply(address) => write!(f, "No reply from {}", address),
+            ShellyRpcError::NotConnected(device) => write!(f, "{} is not connected", device),
+            ShellyRpcError::IoError(e) => write!(f, "I/O error: {}", e),
         }
     }
 }
@@ -43,6 +80,24 @@ impl From<serde_json::Error> for ShellyRpcError {
     }
 }
 
+impl From<rumqttc::ClientError> for ShellyRpcError {
+    fn from(err: rumqttc::ClientError) -> Self {
+        ShellyRpcError::MqttClientError(err)
+    }
+}
+
+impl From<rumqttc::ConnectionError> for ShellyRpcError {
+    fn from(err: rumqttc::ConnectionError) -> Self {
+        ShellyRpcError::MqttConnectionError(err)
+    }
+}
+
+impl From<std::io::Error> for ShellyRpcError {
+    fn from(err: std::io::Error) -> Self {
+        ShellyRpcError::IoError(err)
+    }
+}
+
 impl From<ShellyError> for ShellyRpcError {
     fn from(err: ShellyError) -> Self {
         ShellyRpcError::HttpApiError(err)
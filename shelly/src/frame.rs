@@ -0,0 +1,82 @@
+use crate::error::ShellyRpcError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Seconds to wait for the reply of a device.
+pub(crate) const DEFAULT_TIMEOUT: u64 = 10;
+
+/// Replies to RPC frames awaited by request id, for channels on which replies arrive
+/// separately from the calls, e.g. MQTT and websockets.
+/// See: https://shelly-api-docs.shelly.cloud/gen2/General/RPCProtocol
+#[derive(Debug)]
+pub(crate) struct Replies {
+    pending: Mutex<HashMap<u64, oneshot::Sender<String>>>,
+    next_id: AtomicU64,
+}
+
+impl Replies {
+    pub(crate) fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Returns a copy of `frame` with a new `id` and the given `src`, and the receiver
+    /// of its reply.
+    pub(crate) fn request(
+        &self,
+        frame: &serde_json::Value,
+        src: &str,
+    ) -> (u64, serde_json::Value, oneshot::Receiver<String>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut frame = frame.clone();
+        frame["id"] = id.into();
+        frame["src"] = src.into();
+
+        let (sender, receiver) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id, sender);
+        }
+        (id, frame, receiver)
+    }
+
+    /// Hands the `reply` to the request with its id. Returns whether it was awaited.
+    pub(crate) fn route(&self, reply: String) -> bool {
+        let id = serde_json::from_str::<serde_json::Value>(&reply)
+            .ok()
+            .and_then(|value| value["id"].as_u64());
+        let sender = id.and_then(|id| self.pending.lock().ok()?.remove(&id));
+        match sender {
+            Some(sender) => sender.send(reply).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Waits up to `timeout` for the reply of the request `id` sent to `address`.
+    pub(crate) async fn wait(
+        &self,
+        id: u64,
+        receiver: oneshot::Receiver<String>,
+        timeout: Duration,
+        address: &str,
+    ) -> Result<String, ShellyRpcError> {
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(reply)) => Ok(reply),
+            _ => {
+                self.cancel(id);
+                Err(ShellyRpcError::NoReply(address.to_string()))
+            }
+        }
+    }
+
+    /// Stops waiting for the reply of the request `id`, e.g. when it could not be sent.
+    pub(crate) fn cancel(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+}
@@ -1,3 +1,6 @@
 pub mod api;
 pub mod data;
 pub mod error;
+mod frame;
+pub mod mqtt;
+pub mod outbound;
@@ -0,0 +1,136 @@
+use crate::error::ShellyRpcError;
+use crate::frame::{Replies, DEFAULT_TIMEOUT};
+use log::{debug, trace, warn};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Port of MQTT brokers without TLS.
+const DEFAULT_PORT: u16 = 1883;
+
+/// Session with an MQTT broker carrying RPC calls to Gen2 devices with MQTT enabled.
+/// A device takes calls on `<topic prefix>/rpc`, the prefix defaulting to its device
+/// id, and publishes the reply on `<src>/rpc`, `src` being this session's client id.
+/// See: https://shelly-api-docs.shelly.cloud/gen2/General/RPCChannels#mqtt
+#[derive(Debug)]
+pub struct MqttRpc {
+    client: AsyncClient,
+    src: String,
+    replies: Arc<Replies>,
+    timeout: Duration,
+    event_loop: JoinHandle<()>,
+}
+
+impl MqttRpc {
+    /// Connects to the `broker`, given as `host` or `host:port`, as `src`, which must be
+    /// unique among the broker's clients, and subscribes to the replies.
+    pub async fn connect(
+        broker: &str,
+        src: &str,
+        credentials: Option<(&str, &str)>,
+    ) -> Result<Self, ShellyRpcError> {
+        trace!("connect(broker: {broker}, src: {src})");
+        let (host, port) = match broker.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| ShellyRpcError::MqttBrokerError(broker.to_string()))?,
+            ),
+            None => (broker, DEFAULT_PORT),
+        };
+        let mut options = MqttOptions::new(src, host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some((user, password)) = credentials {
+            options.set_credentials(user, password);
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, 16);
+        let reply_topic = format!("{src}/rpc");
+        client
+            .subscribe(reply_topic.as_str(), QoS::AtLeastOnce)
+            .await?;
+        // Wait for the subscription, so that no reply is missed.
+        loop {
+            if let Event::Incoming(Packet::SubAck(_)) = event_loop.poll().await? {
+                break;
+            }
+        }
+        debug!("Connected to {broker} as {src}");
+
+        let replies = Arc::new(Replies::new());
+        let routed = replies.clone();
+        let resubscribe = client.clone();
+        let event_loop = tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::Publish(publish)))
+                        if publish.topic == reply_topic =>
+                    {
+                        let reply = String::from_utf8_lossy(&publish.payload).to_string();
+                        if !routed.route(reply.clone()) {
+                            debug!("Unexpected reply on {reply_topic}: {reply}");
+                        }
+                    }
+                    // The session is clean, subscribe again after a reconnect.
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        let _ = resubscribe
+                            .subscribe(reply_topic.as_str(), QoS::AtLeastOnce)
+                            .await;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("MQTT connection lost, reconnecting: {e}");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            client,
+            src: src.to_string(),
+            replies,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+            event_loop,
+        })
+    }
+
+    /// Sets how long to wait for the reply of a device, defaults to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Publishes the RPC `frame` to the device with the given topic prefix and returns
+    /// the reply. The frame's `id` and `src` are replaced by the session's.
+    pub async fn call(
+        &self,
+        topic_prefix: &str,
+        frame: &serde_json::Value,
+    ) -> Result<String, ShellyRpcError> {
+        let (id, frame, receiver) = self.replies.request(frame, &self.src);
+        let published = self
+            .client
+            .publish(
+                format!("{topic_prefix}/rpc"),
+                QoS::AtLeastOnce,
+                false,
+                frame.to_string(),
+            )
+            .await;
+        if let Err(e) = published {
+            self.replies.cancel(id);
+            return Err(e.into());
+        }
+        self.replies
+            .wait(id, receiver, self.timeout, topic_prefix)
+            .await
+    }
+}
+
+impl Drop for MqttRpc {
+    fn drop(&mut self) {
+        self.event_loop.abort();
+    }
+}
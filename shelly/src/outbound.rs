@@ -0,0 +1,265 @@
+use crate::error::ShellyRpcError;
+use crate::frame::{Replies, DEFAULT_TIMEOUT};
+use futures_util::{SinkExt, StreamExt};
+use log::{info, trace, warn};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{
+    Callback, ErrorResponse, Request, Response,
+};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Connections by device id.
+type Devices = Arc<Mutex<HashMap<String, Connection>>>;
+
+/// The websocket of a device: the frames to send and the replies awaited on it, so
+/// that a reply only resolves a call made over the same connection.
+#[derive(Debug, Clone)]
+struct Connection {
+    sender: mpsc::UnboundedSender<String>,
+    replies: Arc<Replies>,
+}
+
+/// Server accepting the outbound websockets of Gen2 devices, which connect to it once
+/// configured with `Ws.SetConfig`, e.g.
+/// `{"enable": true, "server": "ws://<server>:8080/?token=<token>"}`.
+/// Devices behind NAT thus take RPC calls over the connection they opened. Connections
+/// without the shared token are refused. A device is identified by the `src` of its
+/// first frame, its device id, and a second connection of a connected device is closed.
+/// See: https://shelly-api-docs.shelly.cloud/gen2/ComponentsAndServices/Ws
+#[derive(Debug)]
+pub struct OutboundServer {
+    src: String,
+    local_addr: SocketAddr,
+    devices: Devices,
+    connected: tokio::sync::Mutex<mpsc::UnboundedReceiver<String>>,
+    timeout: Duration,
+    accept_loop: JoinHandle<()>,
+}
+
+impl OutboundServer {
+    /// Listens on `address`, e.g. `0.0.0.0:8080`, sending calls as `src`. Devices must
+    /// pass the shared `token` in the query of the server URL.
+    pub async fn bind(address: &str, src: &str, token: &str) -> Result<Self, ShellyRpcError> {
+        trace!("bind(address: {address}, src: {src})");
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        info!("Waiting for devices on ws://{local_addr}");
+
+        let devices = Devices::default();
+        let (connected_sender, connected) = mpsc::unbounded_channel();
+        let accept_loop = tokio::spawn({
+            let devices = devices.clone();
+            let token: Arc<str> = token.into();
+            async move {
+                while let Ok((stream, peer)) = listener.accept().await {
+                    tokio::spawn(serve(
+                        stream,
+                        peer,
+                        token.clone(),
+                        devices.clone(),
+                        connected_sender.clone(),
+                    ));
+                }
+            }
+        });
+
+        Ok(Self {
+            src: src.to_string(),
+            local_addr,
+            devices,
+            connected: tokio::sync::Mutex::new(connected),
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+            accept_loop,
+        })
+    }
+
+    /// Sets how long to wait for the reply of a device, defaults to 10 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the address the server listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the ids of the connected devices in order.
+    pub fn devices(&self) -> Vec<String> {
+        let mut devices: Vec<String> = self
+            .devices
+            .lock()
+            .map(|devices| devices.keys().cloned().collect())
+            .unwrap_or_default();
+        devices.sort();
+        devices
+    }
+
+    /// Returns the id of the next device that connects, including reconnects.
+    pub async fn next_connection(&self) -> Option<String> {
+        self.connected.lock().await.recv().await
+    }
+
+    /// Waits up to `timeout` for the device with the given id to connect.
+    pub async fn wait_for(&self, device: &str, timeout: Duration) -> Result<(), ShellyRpcError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while !self.is_connected(device) {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ShellyRpcError::NotConnected(device.to_string()));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
+
+    fn is_connected(&self, device: &str) -> bool {
+        self.devices
+            .lock()
+            .map(|devices| devices.contains_key(device))
+            .unwrap_or_default()
+    }
+
+    /// Sends the RPC `frame` over the connection of the device with the given id and
+    /// returns the reply. The frame's `id` and `src` are replaced by the server's.
+    pub async fn call(
+        &self,
+        device: &str,
+        frame: &serde_json::Value,
+    ) -> Result<String, ShellyRpcError> {
+        let connection = self
+            .devices
+            .lock()
+            .ok()
+            .and_then(|devices| devices.get(device).cloned())
+            .ok_or_else(|| ShellyRpcError::NotConnected(device.to_string()))?;
+
+        let replies = &connection.replies;
+        let (id, frame, receiver) = replies.request(frame, &self.src);
+        if connection.sender.send(frame.to_string()).is_err() {
+            replies.cancel(id);
+            return Err(ShellyRpcError::NotConnected(device.to_string()));
+        }
+        replies.wait(id, receiver, self.timeout, device).await
+    }
+}
+
+impl Drop for OutboundServer {
+    fn drop(&mut self) {
+        self.accept_loop.abort();
+    }
+}
+
+/// Serves the websocket of a device until it is closed, registering the device by the
+/// `src` of its first frame and routing its replies.
+async fn serve(
+    stream: TcpStream,
+    peer: SocketAddr,
+    token: Arc<str>,
+    devices: Devices,
+    connected: mpsc::UnboundedSender<String>,
+) {
+    let authorize = Authorize(&token);
+    let websocket = match tokio_tungstenite::accept_hdr_async(stream, authorize).await {
+        Ok(websocket) => websocket,
+        Err(e) => {
+            warn!("Refused {peer}: {e}");
+            return;
+        }
+    };
+    let (mut sink, mut stream) = websocket.split();
+    let (sender, mut frames) = mpsc::unbounded_channel::<String>();
+    let connection = Connection {
+        sender,
+        replies: Arc::new(Replies::new()),
+    };
+    let mut device: Option<String> = None;
+
+    loop {
+        tokio::select! {
+            Some(frame) = frames.recv() => {
+                if sink.send(Message::Text(frame)).await.is_err() {
+                    break;
+                }
+            }
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    if device.is_none() {
+                        device = serde_json::from_str::<serde_json::Value>(&text)
+                            .ok()
+                            .and_then(|value| value["src"].as_str().map(str::to_string));
+                        if let Some(id) = &device {
+                            if !register(&devices, id, &connection) {
+                                warn!("Refused {peer}: {id} is already connected");
+                                let _ = sink.send(Message::Close(None)).await;
+                                return;
+                            }
+                            info!("{id} connected from {peer}");
+                            let _ = connected.send(id.clone());
+                        }
+                    }
+                    if !connection.replies.route(text.clone()) {
+                        trace!("Notification from {peer}: {text}");
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+
+    if let Some(id) = device {
+        info!("{id} disconnected");
+        if let Ok(mut devices) = devices.lock() {
+            // Leave a newer connection of the device alone.
+            if devices
+                .get(&id)
+                .is_some_and(|other| other.sender.same_channel(&connection.sender))
+            {
+                devices.remove(&id);
+            }
+        }
+    }
+}
+
+/// Handshake callback refusing requests whose URL query does not carry `token=<token>`.
+struct Authorize<'a>(&'a str);
+
+impl Callback for Authorize<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let authorized = request.uri().query().is_some_and(|query| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .any(|(name, value)| name == "token" && value == self.0)
+        });
+        if authorized {
+            return Ok(response);
+        }
+        let mut refusal = ErrorResponse::new(Some("Invalid token".to_string()));
+        *refusal.status_mut() = StatusCode::UNAUTHORIZED;
+        Err(refusal)
+    }
+}
+
+/// Registers the connection of the device with the given id, unless another connection
+/// of the device is still open.
+fn register(devices: &Devices, id: &str, connection: &Connection) -> bool {
+    let Ok(mut devices) = devices.lock() else {
+        return false;
+    };
+    if devices
+        .get(id)
+        .is_some_and(|other| !other.sender.is_closed())
+    {
+        return false;
+    }
+    devices.insert(id.to_string(), connection.clone());
+    true
+}
@@ -1,5 +1,20 @@
-use mockito::Server;
-use shelly::api::Gen2DeviceClient;
+use bytes::BytesMut;
+use futures_util::{SinkExt, StreamExt};
+use mockito::{Matcher, Server};
+use rumqttc::mqttbytes::v4::{
+    read, ConnAck, ConnectReturnCode, Packet, PingResp, PubAck, Publish, SubAck,
+    SubscribeReasonCode,
+};
+use rumqttc::mqttbytes::{Error as MqttBytesError, QoS};
+use shelly::api::{Gen1DeviceClient, Gen2DeviceClient};
+use shelly::error::ShellyRpcError;
+use shelly::mqtt::MqttRpc;
+use shelly::outbound::OutboundServer;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
 
 #[tokio::test]
 async fn get_time() {
@@ -122,6 +137,136 @@ async fn get_location() {
     assert_eq!(lon, longitude);
 }
 
+#[tokio::test]
+async fn get_device_info() {
+    // arrange
+    let expected_body = r#"{"id":1,"method":"Shelly.GetDeviceInfo"}"#;
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus2pm-a8032ab636ec",
+      "result": {
+        "name": null,
+        "id": "shellyplus2pm-a8032ab636ec",
+        "mac": "A8032AB636EC",
+        "model": "SNSW-102P16EU",
+        "gen": 2,
+        "fw_id": "20220527-091739/0.10.2-beta4-gecc3a61",
+        "ver": "0.10.2-beta4",
+        "app": "Plus2PM",
+        "auth_en": false,
+        "auth_domain": null,
+        "profile": "cover"
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body)
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_device_info().await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!("SNSW-102P16EU", result.model);
+    assert_eq!(2, result.generation);
+    assert_eq!(Some("cover".to_string()), result.profile);
+}
+
+#[tokio::test]
+async fn list_methods() {
+    // arrange
+    let expected_body = r#"{"id":1,"method":"Shelly.ListMethods"}"#;
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1-a8032abe54dc",
+      "result": {
+        "methods": ["Shelly.GetDeviceInfo", "Schedule.Create", "KVS.Get"]
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body)
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.list_methods().await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(
+        vec!["Shelly.GetDeviceInfo", "Schedule.Create", "KVS.Get"],
+        result
+    );
+}
+
+#[tokio::test]
+async fn get_components_paged() {
+    // arrange
+    let page = |offset: usize, keys: &[&str]| {
+        (
+            serde_json::json!({
+                "id": 1,
+                "method": "Shelly.GetComponents",
+                "params": { "offset": offset }
+            }),
+            serde_json::json!({
+              "id": 1,
+              "src": "shellyplus2pm-a8032ab636ec",
+              "result": {
+                "components": keys.iter().map(|key| serde_json::json!({ "key": key })).collect::<Vec<_>>(),
+                "cfg_rev": 10,
+                "offset": offset,
+                "total": 3
+              }
+            }),
+        )
+    };
+    let (first_body, first_response) = page(0, &["sys", "switch:0"]);
+    let (second_body, second_response) = page(2, &["switch:1"]);
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let first_mock = server
+        .mock("POST", "/rpc")
+        .match_body(first_body.to_string().as_str())
+        .with_body(first_response.to_string())
+        .create_async()
+        .await;
+    let second_mock = server
+        .mock("POST", "/rpc")
+        .match_body(second_body.to_string().as_str())
+        .with_body(second_response.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_components().await.unwrap();
+
+    // assert
+    first_mock.assert_async().await;
+    second_mock.assert_async().await;
+    assert_eq!(vec!["sys", "switch:0", "switch:1"], result);
+}
+
 #[tokio::test]
 async fn get_value() {
     // arrange
@@ -164,6 +309,82 @@ async fn get_value() {
     assert_eq!(value, result);
 }
 
+#[tokio::test]
+async fn get_value_with_digest_auth() {
+    // arrange
+    let key = "test.key";
+    let value = "42";
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1-a8032abe54dc",
+      "result": {
+        "etag": "0DWty8HwCB",
+        "value": value
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let challenge_mock = server
+        .mock("POST", "/rpc")
+        .match_header("authorization", Matcher::Missing)
+        .with_status(401)
+        .with_header(
+            "www-authenticate",
+            r#"Digest qop="auth", realm="shellyplus1-a8032abe54dc", nonce="60dc59c6", algorithm=SHA-256"#,
+        )
+        .create_async()
+        .await;
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_header(
+            "authorization",
+            Matcher::Regex(
+                r#"^Digest username="admin", realm="shellyplus1-a8032abe54dc", nonce="60dc59c6", uri="/rpc", algorithm=SHA-256, response="[0-9a-f]{64}""#
+                    .to_string(),
+            ),
+        )
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host).with_password(Some("secret"));
+
+    // act
+    let result = uut.get_value(key).await.unwrap();
+
+    // assert
+    challenge_mock.assert_async().await;
+    mock.assert_async().await;
+    assert_eq!(value, result);
+}
+
+#[tokio::test]
+async fn get_value_without_password() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .with_status(401)
+        .with_header(
+            "www-authenticate",
+            r#"Digest qop="auth", realm="shellyplus1-a8032abe54dc", nonce="60dc59c6", algorithm=SHA-256"#,
+        )
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_value("test.key").await;
+
+    // assert
+    mock.assert_async().await;
+    assert!(result.is_err(), "Expected Error is Ok");
+}
+
 #[tokio::test]
 async fn set_value() {
     // arrange
@@ -208,12 +429,158 @@ async fn set_value() {
     assert_eq!(rev, result);
 }
 
+#[tokio::test]
+async fn delete_value() {
+    // arrange
+    let key = "test.key";
+    let rev = 2734;
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "KVS.Delete",
+        "params": {
+            "key": key
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1-a8032abe54dc",
+      "result": {
+        "rev": rev
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body.to_string().as_str())
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.delete_value(key).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(rev, result);
+}
+
+#[tokio::test]
+async fn get_switch_status() {
+    // arrange
+    let id = 1;
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "Switch.GetStatus",
+        "params": {
+            "id": id
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellypro4pm-f008d1d8b8b8",
+      "result": {
+        "id": id,
+        "source": "timer",
+        "output": true,
+        "timer_started_at": 1626935739.79,
+        "timer_duration": 60.0,
+        "apower": 8.9,
+        "voltage": 237.5,
+        "current": 0.04,
+        "pf": 0.95,
+        "aenergy": {
+          "total": 6.532,
+          "by_minute": [45.199, 47.141, 88.397],
+          "minute_ts": 1626935779
+        },
+        "temperature": {
+          "tC": 23.5,
+          "tF": 74.4
+        }
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body.to_string().as_str())
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_switch_status(id).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(id, result.id);
+    assert!(result.output);
+    assert_eq!(Some(60.0), result.timer_duration);
+    assert_eq!(Some(8.9), result.apower);
+    assert_eq!(Some(0.04), result.current);
+    assert_eq!(6.532, result.aenergy.unwrap().total);
+}
+
+#[tokio::test]
+async fn set_switch() {
+    // arrange
+    let id = 2;
+    let toggle_after = 3600;
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "Switch.Set",
+        "params": {
+            "id": id,
+            "on": true,
+            "toggle_after": toggle_after
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellypro4pm-f008d1d8b8b8",
+      "result": {
+        "was_on": false
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body.to_string().as_str())
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.set_switch(id, true, Some(toggle_after)).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert!(!result);
+}
+
 #[tokio::test]
 async fn connection_refused() {
     // arrange
     let host = "localhost";
 
-    let uut = Gen2DeviceClient::new(&host);
+    let uut = Gen2DeviceClient::new(host);
 
     // act
     let result = uut.list_schedule().await;
@@ -288,3 +655,845 @@ async fn key_not_found() {
     assert!(result.is_err(), "Expected Error is Ok");
     println!("{}", result.err().unwrap());
 }
+
+#[tokio::test]
+async fn get_light_status() {
+    // arrange
+    let id = 0;
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "Light.GetStatus",
+        "params": {
+            "id": id
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplusdimmer-441793d6c0fc",
+      "result": {
+        "id": id,
+        "source": "schedule",
+        "output": true,
+        "brightness": 80,
+        "temperature": {
+          "tC": 41.2,
+          "tF": 106.2
+        }
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body.to_string().as_str())
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_light_status(id).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert!(result.output);
+    assert_eq!(80.0, result.brightness);
+    assert!(result.apower.is_none());
+}
+
+#[tokio::test]
+async fn set_light() {
+    // arrange
+    let id = 0;
+    let toggle_after = 3600;
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "Light.Set",
+        "params": {
+            "id": id,
+            "on": true,
+            "brightness": 80,
+            "toggle_after": toggle_after
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplusdimmer-441793d6c0fc",
+      "result": null
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body.to_string().as_str())
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.set_light(id, true, Some(80), Some(toggle_after)).await;
+
+    // assert
+    mock.assert_async().await;
+    assert!(result.is_ok(), "Expected Ok is Error");
+}
+
+#[tokio::test]
+async fn get_cct_status() {
+    // arrange
+    let id = 0;
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "CCT.GetStatus",
+        "params": {
+            "id": id
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplusrgbwpm-a0a3b3c4d5e6",
+      "result": {
+        "id": id,
+        "source": "schedule",
+        "output": false,
+        "brightness": 100,
+        "ct": 2700,
+        "apower": 0.0,
+        "current": 0.0
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body.to_string().as_str())
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_cct_status(id).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert!(!result.output);
+    assert_eq!(Some(0.0), result.apower);
+}
+
+#[tokio::test]
+async fn set_rgbw() {
+    // arrange
+    let id = 0;
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "RGBW.Set",
+        "params": {
+            "id": id,
+            "on": true,
+            "brightness": 60,
+            "rgb": [255, 147, 41]
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplusrgbwpm-a0a3b3c4d5e6",
+      "result": null
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body.to_string().as_str())
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut
+        .set_rgbw(id, true, Some(60), Some([255, 147, 41]), None)
+        .await;
+
+    // assert
+    mock.assert_async().await;
+    assert!(result.is_ok(), "Expected Ok is Error");
+}
+
+#[tokio::test]
+async fn gen1_get_shelly() {
+    // arrange
+    let mock_body = serde_json::json!({
+      "type": "SHSW-1",
+      "mac": "98CDAC1F03A8",
+      "auth": false,
+      "fw": "20230913-112003/v1.14.0-gcb84623",
+      "discoverable": false,
+      "longid": 1,
+      "num_outputs": 1
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("GET", "/shelly")
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen1DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_shelly().await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(Some("SHSW-1".to_string()), result.model);
+    assert_eq!(1, result.generation());
+}
+
+#[tokio::test]
+async fn gen1_get_shelly_of_gen2_device() {
+    // arrange
+    let mock_body = serde_json::json!({
+      "name": null,
+      "id": "shellyplus1pm-a8032abe54dc",
+      "mac": "A8032ABE54DC",
+      "model": "SNSW-001P16EU",
+      "gen": 2,
+      "fw_id": "20231107-164738/1.0.8-g8c7bb8d",
+      "ver": "1.0.8",
+      "app": "Plus1PM",
+      "auth_en": false,
+      "auth_domain": null
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("GET", "/shelly")
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen1DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_shelly().await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(2, result.generation());
+}
+
+#[tokio::test]
+async fn gen1_get_location_and_time() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let settings_mock = server
+        .mock("GET", "/settings")
+        .with_body(
+            serde_json::json!({
+              "device": { "type": "SHSW-1", "mac": "98CDAC1F03A8", "hostname": "shelly1-98CDAC1F03A8" },
+              "lat": 52.516293,
+              "lng": 13.377713,
+              "tzautodetect": true,
+              "time": "06:00",
+              "fw": "20230913-112003/v1.14.0-gcb84623"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    let status_mock = server
+        .mock("GET", "/status")
+        .with_body(serde_json::json!({ "time": "06:00", "unixtime": 1703048400 }).to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen1DeviceClient::new(&host);
+
+    // act
+    let location = uut.get_location().await.unwrap();
+    let time = uut.get_time().await.unwrap();
+
+    // assert
+    settings_mock.assert_async().await;
+    status_mock.assert_async().await;
+    assert_eq!((52.516293, 13.377713), location);
+    assert_eq!(1703048400, time);
+}
+
+#[tokio::test]
+async fn gen1_set_relay() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("GET", "/relay/0")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("turn".into(), "on".into()),
+            Matcher::UrlEncoded("timer".into(), "3600".into()),
+        ]))
+        .with_body(
+            serde_json::json!({
+              "ison": true,
+              "has_timer": true,
+              "timer_started": 1703048400,
+              "timer_duration": 3600,
+              "timer_remaining": 3600,
+              "source": "http"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let uut = Gen1DeviceClient::new(&host);
+
+    // act
+    let result = uut.set_relay(0, true, Some(3600)).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert!(result.ison);
+    assert_eq!(3600, result.timer_remaining);
+}
+
+#[tokio::test]
+async fn gen1_set_schedule_rules() {
+    // arrange
+    let rules = vec![
+        "0600-0123456-on".to_string(),
+        "0814-0123456-off".to_string(),
+    ];
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("GET", "/settings/relay/0")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("schedule".into(), "true".into()),
+            Matcher::UrlEncoded(
+                "schedule_rules".into(),
+                "0600-0123456-on,0814-0123456-off".into(),
+            ),
+        ]))
+        .match_header("authorization", "Basic YWRtaW46c2VjcmV0")
+        .with_body(
+            serde_json::json!({
+              "name": null,
+              "ison": false,
+              "default_state": "off",
+              "auto_on": 0.0,
+              "auto_off": 0.0,
+              "schedule": true,
+              "schedule_rules": rules
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let uut = Gen1DeviceClient::new(&host).with_password(Some("secret"));
+
+    // act
+    let result = uut.set_schedule_rules(0, &rules).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert!(result.schedule);
+    assert_eq!(rules, result.schedule_rules);
+}
+
+#[tokio::test]
+async fn gen1_bad_request() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("GET", "/relay/1")
+        .with_status(404)
+        .with_body("Not Found\n")
+        .create_async()
+        .await;
+
+    let uut = Gen1DeviceClient::new(&host);
+
+    // act
+    let result = uut.get_relay_status(1).await;
+
+    // assert
+    mock.assert_async().await;
+    match result {
+        Err(shelly::error::ShellyRpcError::HttpStatusError(status, message)) => {
+            assert_eq!(404, status);
+            assert_eq!("Not Found", message);
+        }
+        _ => panic!("Unexpected result {result:?}"),
+    }
+}
+
+#[tokio::test]
+async fn list_scripts() {
+    // arrange
+    let expected_body = r#"{"id":1,"method":"Script.List"}"#;
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1pm-a8032abe54dc",
+      "result": {
+        "scripts": [
+          { "id": 1, "name": "dle-default-0", "enable": true, "running": true },
+          { "id": 3, "name": "button", "enable": false, "running": false }
+        ]
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body)
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.list_scripts().await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(2, result.len());
+    assert_eq!("dle-default-0", result[0].name);
+    assert!(result[0].running);
+}
+
+#[tokio::test]
+async fn put_script_code() {
+    // arrange
+    let code = "let CONFIG = {};";
+
+    let expected_body = serde_json::json!({
+        "id": 1,
+        "method": "Script.PutCode",
+        "params": {
+            "id": 1,
+            "code": code,
+            "append": true
+        }
+    });
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1pm-a8032abe54dc",
+      "result": { "len": 1040 }
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(Matcher::Json(expected_body))
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.put_script_code(1, code, true).await.unwrap();
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(1040, result);
+}
+
+#[tokio::test]
+async fn delete_script() {
+    // arrange
+    let expected_body = r#"{"id":1,"method":"Script.Delete","params":{"id":1}}"#;
+
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1pm-a8032abe54dc",
+      "result": null
+    });
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(expected_body)
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    let uut = Gen2DeviceClient::new(&host);
+
+    // act
+    let result = uut.delete_script(1).await;
+
+    // assert
+    mock.assert_async().await;
+    assert!(result.is_ok(), "Expected Ok is Error");
+}
+
+#[tokio::test]
+async fn mqtt_get_time() {
+    // arrange
+    let unix_timestamp = 1654694407;
+    let broker = mqtt_device("shellyplus1pm-a8032abe54dc", move |frame| {
+        assert_eq!("Sys.GetStatus", frame["method"]);
+        Some(serde_json::json!({
+            "result": { "time": "16:20", "unixtime": unix_timestamp }
+        }))
+    })
+    .await;
+    let mqtt = MqttRpc::connect(&broker, "daylight-extender-test", None)
+        .await
+        .unwrap();
+
+    // act
+    let client = Gen2DeviceClient::new("shellyplus1pm-a8032abe54dc").with_mqtt(Some(&mqtt));
+    let actual = client.get_time().await;
+
+    // assert
+    assert_eq!(unix_timestamp, actual.unwrap());
+}
+
+#[tokio::test]
+async fn mqtt_digest_auth() {
+    // arrange
+    let broker = mqtt_device("shellyplus1pm-a8032abe54dc", |frame| {
+        if frame["auth"]["username"] != "admin" || frame["auth"]["nonce"] != 1625038776 {
+            return Some(serde_json::json!({
+                "error": {
+                    "code": 401,
+                    "message": r#"{"auth_type": "digest", "nonce": 1625038776, "nc": 1, "realm": "shellyplus1pm-a8032abe54dc", "algorithm": "SHA-256"}"#
+                }
+            }));
+        }
+        assert_eq!("shellyplus1pm-a8032abe54dc", frame["auth"]["realm"]);
+        assert_eq!(64, frame["auth"]["response"].as_str().unwrap().len());
+        Some(serde_json::json!({ "result": { "etag": "0DWty8HwCB", "rev": 12 } }))
+    })
+    .await;
+    let mqtt = MqttRpc::connect(&broker, "daylight-extender-test", None)
+        .await
+        .unwrap();
+
+    // act
+    let client = Gen2DeviceClient::new("shellyplus1pm-a8032abe54dc")
+        .with_password(Some("secret"))
+        .with_mqtt(Some(&mqtt));
+    let actual = client.set_value("key", "value").await;
+
+    // assert
+    assert_eq!(12, actual.unwrap());
+}
+
+#[tokio::test]
+async fn mqtt_no_reply() {
+    // arrange
+    let broker = mqtt_device("shellyplus1pm-a8032abe54dc", |_| None).await;
+    let mqtt = MqttRpc::connect(&broker, "daylight-extender-test", None)
+        .await
+        .unwrap()
+        .with_timeout(Duration::from_millis(200));
+
+    // act
+    let client = Gen2DeviceClient::new("shellyplus1pm-a8032abe54dc").with_mqtt(Some(&mqtt));
+    let actual = client.get_time().await;
+
+    // assert
+    assert!(matches!(actual, Err(ShellyRpcError::NoReply(_))));
+}
+
+#[tokio::test]
+async fn outbound_get_time() {
+    // arrange
+    let unix_timestamp = 1654694407;
+    let server = OutboundServer::bind("127.0.0.1:0", "daylight-extender-test", "secret")
+        .await
+        .unwrap();
+    outbound_device(
+        server.local_addr(),
+        "secret",
+        "shellyplus1pm-a8032abe54dc",
+        move |frame| {
+            assert_eq!("Sys.GetStatus", frame["method"]);
+            serde_json::json!({ "result": { "time": "16:20", "unixtime": unix_timestamp } })
+        },
+    )
+    .await;
+    let connected = server.next_connection().await;
+
+    // act
+    let client = Gen2DeviceClient::new("shellyplus1pm-a8032abe54dc").with_outbound(Some(&server));
+    let actual = client.get_time().await;
+
+    // assert
+    assert_eq!(Some("shellyplus1pm-a8032abe54dc".to_string()), connected);
+    assert_eq!(vec!["shellyplus1pm-a8032abe54dc"], server.devices());
+    assert_eq!(unix_timestamp, actual.unwrap());
+}
+
+#[tokio::test]
+async fn outbound_not_connected() {
+    // arrange
+    let server = OutboundServer::bind("127.0.0.1:0", "daylight-extender-test", "secret")
+        .await
+        .unwrap();
+
+    // act
+    let waited = server
+        .wait_for("shellyplus1pm-a8032abe54dc", Duration::from_millis(200))
+        .await;
+    let client = Gen2DeviceClient::new("shellyplus1pm-a8032abe54dc").with_outbound(Some(&server));
+    let actual = client.get_time().await;
+
+    // assert
+    assert!(matches!(waited, Err(ShellyRpcError::NotConnected(_))));
+    assert!(matches!(actual, Err(ShellyRpcError::NotConnected(_))));
+}
+
+#[tokio::test]
+async fn outbound_invalid_token() {
+    // arrange
+    let server = OutboundServer::bind("127.0.0.1:0", "daylight-extender-test", "secret")
+        .await
+        .unwrap();
+    let address = server.local_addr();
+    let stream = TcpStream::connect(address).await.unwrap();
+
+    // act
+    let actual =
+        tokio_tungstenite::client_async(format!("ws://{address}/?token=guess"), stream).await;
+
+    // assert
+    assert!(actual.is_err());
+    assert!(server.devices().is_empty());
+}
+
+#[tokio::test]
+async fn outbound_duplicate_device() {
+    // arrange
+    let unix_timestamp = 1654694407;
+    let server = OutboundServer::bind("127.0.0.1:0", "daylight-extender-test", "secret")
+        .await
+        .unwrap();
+    outbound_device(
+        server.local_addr(),
+        "secret",
+        "shellyplus1pm-a8032abe54dc",
+        move |_| serde_json::json!({ "result": { "time": "16:20", "unixtime": unix_timestamp } }),
+    )
+    .await;
+    server.next_connection().await;
+
+    let address = server.local_addr();
+    let stream = TcpStream::connect(address).await.unwrap();
+    let (mut websocket, _) =
+        tokio_tungstenite::client_async(format!("ws://{address}/?token=secret"), stream)
+            .await
+            .unwrap();
+    let status = serde_json::json!({
+        "src": "shellyplus1pm-a8032abe54dc",
+        "dst": "ws",
+        "method": "NotifyFullStatus",
+        "params": { "ts": 1654694407.25 }
+    });
+
+    // act
+    websocket
+        .send(Message::Text(status.to_string()))
+        .await
+        .unwrap();
+    let closed = websocket.next().await;
+    let client = Gen2DeviceClient::new("shellyplus1pm-a8032abe54dc").with_outbound(Some(&server));
+    let actual = client.get_time().await;
+
+    // assert
+    assert!(matches!(closed, Some(Ok(Message::Close(_))) | None));
+    assert_eq!(vec!["shellyplus1pm-a8032abe54dc"], server.devices());
+    assert_eq!(unix_timestamp, actual.unwrap());
+}
+
+#[tokio::test]
+async fn outbound_reply_of_other_device() {
+    // arrange
+    let unix_timestamp = 1654694407;
+    let server = OutboundServer::bind("127.0.0.1:0", "daylight-extender-test", "secret")
+        .await
+        .unwrap();
+    let address = server.local_addr();
+    let mut device = outbound_websocket(address, "secret", "shellyplus1pm-a8032abe54dc").await;
+    server.next_connection().await;
+    let mut other = outbound_websocket(address, "secret", "shellyplus1pm-441793d69718").await;
+    server.next_connection().await;
+
+    // act
+    let client = Gen2DeviceClient::new("shellyplus1pm-a8032abe54dc").with_outbound(Some(&server));
+    let (actual, _) = tokio::join!(client.get_time(), async {
+        let Some(Ok(Message::Text(text))) = device.next().await else {
+            panic!("no frame sent to the device");
+        };
+        let frame: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let forged = serde_json::json!({
+            "id": frame["id"],
+            "src": "shellyplus1pm-441793d69718",
+            "dst": frame["src"],
+            "result": { "time": "00:00", "unixtime": 0 }
+        });
+        other.send(Message::Text(forged.to_string())).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let answer = serde_json::json!({
+            "id": frame["id"],
+            "src": "shellyplus1pm-a8032abe54dc",
+            "dst": frame["src"],
+            "result": { "time": "16:20", "unixtime": unix_timestamp }
+        });
+        device
+            .send(Message::Text(answer.to_string()))
+            .await
+            .unwrap();
+    });
+
+    // assert
+    assert_eq!(unix_timestamp, actual.unwrap());
+}
+
+/// Connects a device with `token` to the outbound websocket server at `address`, which
+/// announces itself with `NotifyFullStatus` and answers the RPC frames with `reply`.
+async fn outbound_device<F>(address: std::net::SocketAddr, token: &str, device: &str, reply: F)
+where
+    F: Fn(&serde_json::Value) -> serde_json::Value + Send + 'static,
+{
+    let mut websocket = outbound_websocket(address, token, device).await;
+    let device = device.to_string();
+    tokio::spawn(async move {
+        while let Some(Ok(Message::Text(text))) = websocket.next().await {
+            let frame: serde_json::Value = serde_json::from_str(&text).unwrap();
+            let mut answer = reply(&frame);
+            answer["id"] = frame["id"].clone();
+            answer["src"] = device.as_str().into();
+            answer["dst"] = frame["src"].clone();
+            websocket
+                .send(Message::Text(answer.to_string()))
+                .await
+                .unwrap();
+        }
+    });
+}
+
+/// Opens the websocket of a device with `token` to the outbound websocket server at
+/// `address` and announces the device with `NotifyFullStatus`.
+async fn outbound_websocket(
+    address: std::net::SocketAddr,
+    token: &str,
+    device: &str,
+) -> WebSocketStream<TcpStream> {
+    let stream = TcpStream::connect(address).await.unwrap();
+    let url = format!("ws://{address}/?token={token}");
+    let (mut websocket, _) = tokio_tungstenite::client_async(url, stream).await.unwrap();
+    let status = serde_json::json!({
+        "src": device,
+        "dst": "ws",
+        "method": "NotifyFullStatus",
+        "params": { "ts": 1654694407.25 }
+    });
+    websocket
+        .send(Message::Text(status.to_string()))
+        .await
+        .unwrap();
+    websocket
+}
+
+/// Starts a broker with a single device, which answers the RPC frames published to
+/// `<device>/rpc` with the frame returned by `reply`, if any. Returns the address of
+/// the broker.
+async fn mqtt_device<F>(device: &str, reply: F) -> String
+where
+    F: Fn(&serde_json::Value) -> Option<serde_json::Value> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let rpc_topic = format!("{device}/rpc");
+    let device = device.to_string();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut incoming = BytesMut::new();
+        loop {
+            let packet = match read(&mut incoming, 1 << 20) {
+                Ok(packet) => packet,
+                Err(MqttBytesError::InsufficientBytes(_)) => {
+                    if stream.read_buf(&mut incoming).await.unwrap() == 0 {
+                        return;
+                    }
+                    continue;
+                }
+                Err(e) => panic!("{e}"),
+            };
+            let mut outgoing = BytesMut::new();
+            match packet {
+                Packet::Connect(_) => {
+                    ConnAck::new(ConnectReturnCode::Success, false)
+                        .write(&mut outgoing)
+                        .unwrap();
+                }
+                Packet::Subscribe(subscribe) => {
+                    SubAck::new(
+                        subscribe.pkid,
+                        vec![SubscribeReasonCode::Success(QoS::AtLeastOnce)],
+                    )
+                    .write(&mut outgoing)
+                    .unwrap();
+                }
+                Packet::Publish(publish) => {
+                    if publish.qos == QoS::AtLeastOnce {
+                        PubAck::new(publish.pkid).write(&mut outgoing).unwrap();
+                    }
+                    let frame: serde_json::Value =
+                        serde_json::from_slice(&publish.payload).unwrap();
+                    if let (true, Some(mut answer)) = (publish.topic == rpc_topic, reply(&frame)) {
+                        answer["id"] = frame["id"].clone();
+                        answer["src"] = device.as_str().into();
+                        answer["dst"] = frame["src"].clone();
+                        let topic = format!("{}/rpc", frame["src"].as_str().unwrap());
+                        Publish::new(topic, QoS::AtMostOnce, answer.to_string())
+                            .write(&mut outgoing)
+                            .unwrap();
+                    }
+                }
+                Packet::PingReq => {
+                    PingResp.write(&mut outgoing).unwrap();
+                }
+                Packet::Disconnect => return,
+                _ => {}
+            }
+            stream.write_all(&outgoing).await.unwrap();
+        }
+    });
+    address
+}
@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{Local, LocalResult, NaiveDate, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// A reading of a switch taken by the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub timestamp: i64,
+    pub output: bool,
+    /// Value of the `aenergy.total` counter in watt-hours, if the switch is metered.
+    pub energy_total: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Totals {
+    pub on_seconds: i64,
+    pub energy_wh: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChannelLedger {
+    pub last_sample: Option<Sample>,
+    pub days: BTreeMap<NaiveDate, Totals>,
+}
+
+/// Cumulative lamp on-time and energy per channel, persisted as a JSON file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Ledger {
+    pub channels: BTreeMap<String, ChannelLedger>,
+}
+
+impl Ledger {
+    /// Loads the ledger from `path`, starting an empty one if the file does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Adds the on-time and energy between the channel's previous sample and `sample`
+    /// to the day of `sample`. Gaps longer than `max_gap` seconds, e.g. while the daemon
+    /// was not running, are accounted with the planned lighting `windows` instead, split
+    /// at local midnight so that each day gets the on-time of its own windows and the
+    /// energy in proportion. A decreasing energy counter means the device restarted and
+    /// counts from zero.
+    pub fn record(&mut self, channel: &str, sample: Sample, windows: &[(i64, i64)], max_gap: i64) {
+        let ledger = self.channels.entry(channel.to_string()).or_default();
+        if let Some(previous) = ledger.last_sample {
+            let elapsed = sample.timestamp - previous.timestamp;
+            if elapsed > 0 {
+                let on_seconds: BTreeMap<NaiveDate, i64> = if elapsed > max_gap {
+                    planned_on_seconds(windows, previous.timestamp, sample.timestamp)
+                } else {
+                    let on_seconds = match (previous.output, sample.output) {
+                        (true, true) => elapsed,
+                        (false, false) => 0,
+                        _ => elapsed / 2,
+                    };
+                    BTreeMap::from([(local_date(sample.timestamp), on_seconds)])
+                };
+
+                let energy_wh = match (previous.energy_total, sample.energy_total) {
+                    (Some(previous), Some(current)) if current >= previous => current - previous,
+                    (Some(_), Some(current)) => current,
+                    _ => 0.0,
+                };
+
+                let total: i64 = on_seconds.values().sum();
+                if total == 0 {
+                    let totals = ledger.days.entry(local_date(sample.timestamp)).or_default();
+                    totals.energy_wh += energy_wh;
+                }
+                for (date, seconds) in on_seconds {
+                    let totals = ledger.days.entry(date).or_default();
+                    totals.on_seconds += seconds;
+                    if total > 0 {
+                        totals.energy_wh += energy_wh * seconds as f64 / total as f64;
+                    }
+                }
+            }
+        }
+
+        ledger.last_sample = Some(sample);
+    }
+
+    /// Renders daily and monthly totals per channel. If `lamp_life_hours` is given,
+    /// the lamp hours remaining are estimated from the cumulative on-time.
+    pub fn report(&self, lamp_life_hours: Option<f64>) -> String {
+        let mut out = String::new();
+        for (channel, ledger) in &self.channels {
+            let _ = writeln!(out, "Channel {channel}");
+            let _ = writeln!(
+                out,
+                "  {:<10} {:>9} {:>13}",
+                "Date", "On (h)", "Energy (kWh)"
+            );
+            let mut months: BTreeMap<String, Totals> = BTreeMap::new();
+            for (date, totals) in &ledger.days {
+                let _ = writeln!(
+                    out,
+                    "  {:<10} {:>9.2} {:>13.3}",
+                    date.format("%Y-%m-%d"),
+                    hours(totals.on_seconds),
+                    totals.energy_wh / 1000.0
+                );
+                let month = months.entry(date.format("%Y-%m").to_string()).or_default();
+                month.on_seconds += totals.on_seconds;
+                month.energy_wh += totals.energy_wh;
+            }
+
+            let _ = writeln!(
+                out,
+                "  {:<10} {:>9} {:>13}",
+                "Month", "On (h)", "Energy (kWh)"
+            );
+            for (month, totals) in &months {
+                let _ = writeln!(
+                    out,
+                    "  {:<10} {:>9.2} {:>13.3}",
+                    month,
+                    hours(totals.on_seconds),
+                    totals.energy_wh / 1000.0
+                );
+            }
+
+            let total_hours = hours(ledger.days.values().map(|t| t.on_seconds).sum());
+            match lamp_life_hours {
+                Some(life) => {
+                    let _ = writeln!(
+                        out,
+                        "  Lamp hours: {total_hours:.2}, estimated remaining: {:.2}",
+                        (life - total_hours).max(0.0)
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "  Lamp hours: {total_hours:.2}");
+                }
+            }
+        }
+        out
+    }
+}
+
+fn hours(seconds: i64) -> f64 {
+    seconds as f64 / 3600.0
+}
+
+/// Returns the seconds of the `windows` between `start` and `end` by local date.
+fn planned_on_seconds(windows: &[(i64, i64)], start: i64, end: i64) -> BTreeMap<NaiveDate, i64> {
+    let mut on_seconds = BTreeMap::new();
+    for (light_on, light_off) in windows {
+        let mut from = *light_on.max(&start);
+        let to = *light_off.min(&end);
+        while from < to {
+            let until = next_midnight(from).min(to);
+            *on_seconds.entry(local_date(from)).or_default() += until - from;
+            from = until;
+        }
+    }
+    on_seconds
+}
+
+/// Returns the timestamp of the local midnight following `timestamp`.
+fn next_midnight(timestamp: i64) -> i64 {
+    local_date(timestamp)
+        .succ_opt()
+        .and_then(|date| {
+            Local
+                .from_local_datetime(&date.and_time(NaiveTime::MIN))
+                .earliest()
+        })
+        .map_or(i64::MAX, |dt| dt.timestamp())
+}
+
+fn local_date(timestamp: i64) -> NaiveDate {
+    match Local.timestamp_opt(timestamp, 0) {
+        LocalResult::Single(dt) => dt.date_naive(),
+        _ => NaiveDate::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNEL: &str = "192.168.0.232/switch:0";
+
+    fn sample(timestamp: i64, output: bool, energy_total: f64) -> Sample {
+        Sample {
+            timestamp,
+            output,
+            energy_total: Some(energy_total),
+        }
+    }
+
+    /// Returns the timestamp of the given local time in December 2023.
+    fn local(day: u32, hour: u32, min: u32, sec: u32) -> i64 {
+        Local
+            .with_ymd_and_hms(2023, 12, day, hour, min, sec)
+            .single()
+            .expect("Unexpected")
+            .timestamp()
+    }
+
+    #[test]
+    fn record_accumulates_on_time_and_energy() {
+        // arrange
+        let mut ledger = Ledger::default();
+
+        // act
+        // Wednesday, 20 December 2023 06:00:00, 06:05:00 and 06:10:00
+        ledger.record(CHANNEL, sample(1703048400, true, 100.0), &[], 600);
+        ledger.record(CHANNEL, sample(1703048700, true, 105.0), &[], 600);
+        ledger.record(CHANNEL, sample(1703049000, false, 107.5), &[], 600);
+
+        // assert
+        let date = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+        let totals = ledger.channels[CHANNEL].days[&date];
+        assert_eq!(300 + 150, totals.on_seconds);
+        assert_eq!(7.5, totals.energy_wh);
+    }
+
+    #[test]
+    fn record_counter_reset() {
+        // arrange
+        let mut ledger = Ledger::default();
+
+        // act
+        ledger.record(CHANNEL, sample(1703048400, true, 100.0), &[], 600);
+        ledger.record(CHANNEL, sample(1703048700, true, 2.0), &[], 600);
+
+        // assert
+        let date = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+        assert_eq!(2.0, ledger.channels[CHANNEL].days[&date].energy_wh);
+    }
+
+    #[test]
+    fn record_gap_uses_planned_window() {
+        // arrange
+        let mut ledger = Ledger::default();
+        let windows = [(local(20, 3, 53, 24), local(20, 8, 14, 19))];
+
+        // act
+        ledger.record(
+            CHANNEL,
+            sample(local(20, 2, 0, 0), false, 0.0),
+            &windows,
+            600,
+        );
+        ledger.record(
+            CHANNEL,
+            sample(local(20, 12, 0, 0), false, 0.0),
+            &windows,
+            600,
+        );
+
+        // assert
+        let date = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+        assert_eq!(15655, ledger.channels[CHANNEL].days[&date].on_seconds);
+    }
+
+    #[test]
+    fn record_gap_split_at_midnight() {
+        // arrange
+        let mut ledger = Ledger::default();
+        let windows = [
+            (local(19, 22, 0, 0), local(20, 2, 0, 0)),
+            (local(20, 6, 0, 0), local(20, 8, 0, 0)),
+        ];
+
+        // act
+        ledger.record(
+            CHANNEL,
+            sample(local(19, 12, 0, 0), false, 0.0),
+            &windows,
+            600,
+        );
+        ledger.record(
+            CHANNEL,
+            sample(local(20, 12, 0, 0), false, 60.0),
+            &windows,
+            600,
+        );
+
+        // assert
+        let days = &ledger.channels[CHANNEL].days;
+        let date = NaiveDate::from_ymd_opt(2023, 12, 19).expect("Unexpected");
+        assert_eq!(2 * 3600, days[&date].on_seconds);
+        assert_eq!(20.0, days[&date].energy_wh);
+        let date = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+        assert_eq!(4 * 3600, days[&date].on_seconds);
+        assert_eq!(40.0, days[&date].energy_wh);
+    }
+
+    #[test]
+    fn report_remaining_lamp_hours() {
+        // arrange
+        let mut ledger = Ledger::default();
+        ledger.record(CHANNEL, sample(1703048400, true, 0.0), &[], 7200);
+        ledger.record(CHANNEL, sample(1703052000, true, 100.0), &[], 7200);
+
+        // act
+        let report = ledger.report(Some(1000.0));
+
+        // assert
+        assert!(report.contains("Channel 192.168.0.232/switch:0"));
+        assert!(report.contains("  2023-12-20      1.00         0.100"));
+        assert!(report.contains("  2023-12         1.00         0.100"));
+        assert!(report.contains("Lamp hours: 1.00, estimated remaining: 999.00"));
+    }
+}
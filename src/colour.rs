@@ -0,0 +1,201 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The device accepts at most 20 schedule jobs, leave room for the on and off jobs
+/// of both slots.
+pub const MAX_CURVE_STEPS: usize = 6;
+
+/// Colour of a CCT or RGBW light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    /// Colour temperature in kelvin, set with `CCT.Set`.
+    Temperature(u16),
+    /// Red, green and blue channels, set with `RGBW.Set`.
+    Rgb([u8; 3]),
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Colour::Temperature(ct) => write!(f, "{ct}K"),
+            Colour::Rgb([r, g, b]) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+        }
+    }
+}
+
+impl FromStr for Colour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 {
+                return Err(format!("invalid colour '{s}', expected #RRGGBB"));
+            }
+            let channel = |i: usize| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .map_err(|_| format!("invalid colour '{s}', expected #RRGGBB"))
+            };
+            return Ok(Colour::Rgb([channel(0)?, channel(2)?, channel(4)?]));
+        }
+
+        s.trim_end_matches(['K', 'k'])
+            .parse::<u16>()
+            .ok()
+            .filter(|ct| (1000..=10000).contains(ct))
+            .map(Colour::Temperature)
+            .ok_or_else(|| {
+                format!("invalid colour '{s}', expected #RRGGBB or a temperature in kelvin")
+            })
+    }
+}
+
+/// Colours over the lit window, given as points at a fraction of the window.
+///
+/// Parsed from a comma separated list of `fraction=colour` points, e.g.
+/// `0=2700,0.5=5000,1=2700` for colour temperatures in kelvin or
+/// `0=#ff9329,0.5=#ffffff` for RGB colours. The light keeps the colour of a point
+/// until the next one, so a point at `1` has no effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColourCurve {
+    points: Vec<(f64, Colour)>,
+}
+
+impl ColourCurve {
+    /// Returns the colour at `fraction` of the window, the first point's colour
+    /// before the first point.
+    pub fn colour_at(&self, fraction: f64) -> Colour {
+        self.points
+            .iter()
+            .take_while(|(at, _)| *at <= fraction)
+            .last()
+            .unwrap_or(&self.points[0])
+            .1
+    }
+
+    /// Returns the colours set after the light was switched on at `light_on`, as
+    /// `(timestamp, colour)` for every point inside the window `(light_on, light_off)`.
+    pub fn steps(&self, light_on: i64, light_off: i64) -> Vec<(i64, Colour)> {
+        let duration = (light_off - light_on) as f64;
+        self.points
+            .iter()
+            .filter(|(at, _)| *at > 0.0 && *at < 1.0)
+            .map(|(at, colour)| (light_on + (at * duration).round() as i64, *colour))
+            .collect()
+    }
+
+    /// Returns true for colour temperatures, false for RGB colours.
+    pub fn is_temperature(&self) -> bool {
+        matches!(self.points[0].1, Colour::Temperature(_))
+    }
+}
+
+impl FromStr for ColourCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut points = Vec::new();
+        for entry in s.split(',').map(str::trim) {
+            let (at, colour) = entry.split_once('=').ok_or_else(|| {
+                format!("invalid curve point '{entry}', expected fraction=colour")
+            })?;
+            let at = at
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|at| (0.0..=1.0).contains(at))
+                .ok_or_else(|| format!("invalid curve position '{at}', expected 0 -- 1"))?;
+            points.push((at, colour.parse::<Colour>()?));
+        }
+
+        if points.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err("curve points must be in ascending order".to_string());
+        }
+        if points.windows(2).any(|pair| {
+            matches!(pair[0].1, Colour::Temperature(_))
+                != matches!(pair[1].1, Colour::Temperature(_))
+        }) {
+            return Err("curve mixes colour temperatures and RGB colours".to_string());
+        }
+
+        let curve = Self { points };
+        if curve.steps(0, 1_000_000).len() > MAX_CURVE_STEPS {
+            return Err(format!(
+                "curve has more than {MAX_CURVE_STEPS} points between 0 and 1"
+            ));
+        }
+        Ok(curve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("2700", Colour::Temperature(2700))]
+    #[case("5000K", Colour::Temperature(5000))]
+    #[case("#ff9329", Colour::Rgb([255, 147, 41]))]
+    fn colour_from_str_valid(#[case] s: &str, #[case] expected: Colour) {
+        // act
+        let actual = s.parse::<Colour>();
+
+        // assert
+        assert_eq!(expected, actual.expect("Unexpected"));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("#ff93")]
+    #[case("#gg9329")]
+    #[case("200")]
+    fn colour_from_str_invalid(#[case] s: &str) {
+        // act
+        let result = s.parse::<Colour>();
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[rstest]
+    #[case("0.5=2700,0.2=5000")]
+    #[case("0=2700,0.5=#ffffff")]
+    #[case("0=2700,1.5=5000")]
+    #[case("2700")]
+    #[case("0=2700,0.1=2800,0.2=2900,0.3=3000,0.4=3100,0.5=3200,0.6=3300,0.7=3400")]
+    fn curve_from_str_invalid(#[case] s: &str) {
+        // act
+        let result = s.parse::<ColourCurve>();
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[rstest]
+    #[case(0.0, Colour::Temperature(2700))]
+    #[case(0.6, Colour::Temperature(5000))]
+    #[case(0.9, Colour::Temperature(3000))]
+    fn colour_at_parametrized(#[case] fraction: f64, #[case] expected: Colour) {
+        // arrange
+        let curve: ColourCurve = "0.1=2700,0.5=5000,0.8=3000".parse().expect("Unexpected");
+
+        // act
+        let actual = curve.colour_at(fraction);
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn steps_inside_window() {
+        // arrange
+        let curve: ColourCurve = "0=2700,0.5=5000,1=2700".parse().expect("Unexpected");
+
+        // act
+        let actual = curve.steps(1000, 2000);
+
+        // assert
+        assert_eq!(vec![(1500, Colour::Temperature(5000))], actual);
+    }
+}
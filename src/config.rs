@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Deserializer};
+
+use crate::colour::ColourCurve;
+use crate::error::CustomError;
+use crate::kvs::KeyNamespace;
+use crate::output::Output;
+use crate::plan::ExtensionMode;
+use crate::tariff::Tariff;
+
+/// Configuration file shared by all users of the machine.
+pub const SYSTEM_PATH: &str = "/etc/daylight-extender/config.toml";
+
+/// Settings read from a configuration file, e.g.
+///
+/// ```toml
+/// [defaults]
+/// total_day_length = 16
+/// mode = "split"
+/// min_power = 40.0
+///
+/// [devices.barn-1]
+/// host = "192.168.0.232"
+/// password = "secret"
+///
+/// [devices.barn-2-dimmer]
+/// host = "192.168.0.233"
+/// dimmer = "80:30"
+/// ```
+///
+/// The keys are the names of the command line flags with underscores. A device's
+/// settings take precedence over the defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Settings of every device.
+    #[serde(default)]
+    pub defaults: Settings,
+    /// Devices and channels by name.
+    #[serde(default)]
+    pub devices: BTreeMap<String, Settings>,
+}
+
+/// Settings of one device channel, each overriding the built-in default if given.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub host: Option<String>,
+    /// Password of a device with authentication enabled.
+    pub password: Option<String>,
+    pub mqtt_broker: Option<String>,
+    pub mqtt_user: Option<String>,
+    pub mqtt_password: Option<String>,
+    /// Generation of the device, 1 or 2, detected if omitted.
+    pub generation: Option<u8>,
+    pub switch_id: Option<u8>,
+    #[serde(default, deserialize_with = "instance")]
+    pub instance: Option<KeyNamespace>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub dimmer: Option<Output>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub colour_curve: Option<ColourCurve>,
+    #[serde(default, deserialize_with = "percent")]
+    pub colour_brightness: Option<u8>,
+    pub switch_off_job: Option<bool>,
+    pub sun_relative: Option<bool>,
+    pub check_interval: Option<u64>,
+    pub min_power: Option<f64>,
+    pub min_current: Option<f64>,
+    pub verify_delay: Option<u64>,
+    pub ledger: Option<PathBuf>,
+    pub mode: Option<ExtensionMode>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub cheapest_tariff: Option<Tariff>,
+    #[serde(default, deserialize_with = "time_of_day")]
+    pub earliest_on: Option<NaiveTime>,
+    #[serde(default, deserialize_with = "time_of_day")]
+    pub latest_off: Option<NaiveTime>,
+    pub night_break: Option<u64>,
+    #[serde(default, deserialize_with = "hours")]
+    pub total_day_length: Option<u8>,
+    pub profile: Option<String>,
+    pub profile_dir: Option<PathBuf>,
+    pub program: Option<PathBuf>,
+    pub placement_date: Option<NaiveDate>,
+    /// `[latitude, longitude]` of the reference location.
+    pub reference_location: Option<(f64, f64)>,
+}
+
+impl Settings {
+    /// Returns these settings with the ones given in `other` replaced. A setting given in
+    /// `other` also drops the ones it excludes, e.g. a device's `total_day_length`
+    /// replaces a `profile` of the defaults.
+    pub fn merge(mut self, other: Settings) -> Settings {
+        self.clear_excluded_by(&other);
+        Settings {
+            host: other.host.or(self.host),
+            password: other.password.or(self.password),
+            mqtt_broker: other.mqtt_broker.or(self.mqtt_broker),
+            mqtt_user: other.mqtt_user.or(self.mqtt_user),
+            mqtt_password: other.mqtt_password.or(self.mqtt_password),
+            generation: other.generation.or(self.generation),
+            switch_id: other.switch_id.or(self.switch_id),
+            instance: other.instance.or(self.instance),
+            dimmer: other.dimmer.or(self.dimmer),
+            colour_curve: other.colour_curve.or(self.colour_curve),
+            colour_brightness: other.colour_brightness.or(self.colour_brightness),
+            switch_off_job: other.switch_off_job.or(self.switch_off_job),
+            sun_relative: other.sun_relative.or(self.sun_relative),
+            check_interval: other.check_interval.or(self.check_interval),
+            min_power: other.min_power.or(self.min_power),
+            min_current: other.min_current.or(self.min_current),
+            verify_delay: other.verify_delay.or(self.verify_delay),
+            ledger: other.ledger.or(self.ledger),
+            mode: other.mode.or(self.mode),
+            cheapest_tariff: other.cheapest_tariff.or(self.cheapest_tariff),
+            earliest_on: other.earliest_on.or(self.earliest_on),
+            latest_off: other.latest_off.or(self.latest_off),
+            night_break: other.night_break.or(self.night_break),
+            total_day_length: other.total_day_length.or(self.total_day_length),
+            profile: other.profile.or(self.profile),
+            profile_dir: other.profile_dir.or(self.profile_dir),
+            program: other.program.or(self.program),
+            placement_date: other.placement_date.or(self.placement_date),
+            reference_location: other.reference_location.or(self.reference_location),
+        }
+    }
+
+    /// Returns the groups of settings excluding each other, day length, placement and
+    /// output, with whether each setting is given.
+    fn exclusive(&self) -> [Vec<(&'static str, bool)>; 3] {
+        [
+            vec![
+                ("total_day_length", self.total_day_length.is_some()),
+                ("profile", self.profile.is_some()),
+                ("program", self.program.is_some()),
+                ("reference_location", self.reference_location.is_some()),
+            ],
+            vec![
+                ("mode", self.mode.is_some()),
+                ("cheapest_tariff", self.cheapest_tariff.is_some()),
+                ("night_break", self.night_break.is_some()),
+            ],
+            vec![
+                ("dimmer", self.dimmer.is_some()),
+                ("colour_curve", self.colour_curve.is_some()),
+            ],
+        ]
+    }
+
+    /// Clears the groups of excluding settings of which `other` gives one.
+    fn clear_excluded_by(&mut self, other: &Settings) {
+        let [day_length, placement, output] = other
+            .exclusive()
+            .map(|keys| keys.iter().any(|(_, given)| *given));
+        if day_length {
+            self.total_day_length = None;
+            self.profile = None;
+            self.program = None;
+            self.reference_location = None;
+        }
+        if placement {
+            self.mode = None;
+            self.cheapest_tariff = None;
+            self.night_break = None;
+        }
+        if output {
+            self.dimmer = None;
+            self.colour_curve = None;
+        }
+    }
+
+    /// Checks that at most one of the settings excluding each other is given.
+    fn validate(&self) -> Result<(), String> {
+        for keys in self.exclusive() {
+            let given: Vec<&str> = keys
+                .iter()
+                .filter(|(_, given)| *given)
+                .map(|(key, _)| *key)
+                .collect();
+            if given.len() > 1 {
+                return Err(format!("keys {} exclude each other", given.join(" and ")));
+            }
+        }
+
+        if let Some(generation) = self
+            .generation
+            .filter(|generation| !(1..=2).contains(generation))
+        {
+            return Err(format!("key generation {generation} is not 1 or 2"));
+        }
+
+        for (key, minutes) in [
+            ("check_interval", self.check_interval),
+            ("night_break", self.night_break),
+        ] {
+            if minutes == Some(0) {
+                return Err(format!("key {key} 0 is not a positive number of minutes"));
+            }
+        }
+
+        if let Some((latitude, longitude)) = self.reference_location {
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return Err(format!(
+                    "key reference_location [{latitude}, {longitude}] is not a valid location"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Loads the configuration file `path`, or if none is given the system-wide file
+    /// and the user's file, which takes precedence. Missing default files are skipped.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let paths = match path {
+            Some(path) => vec![path.to_path_buf()],
+            None => [Some(PathBuf::from(SYSTEM_PATH)), user_path()]
+                .into_iter()
+                .flatten()
+                .filter(|path| path.is_file())
+                .collect(),
+        };
+
+        let mut config = Config::default();
+        for path in paths {
+            let content = std::fs::read_to_string(&path).map_err(|e| {
+                CustomError::InvalidConfig(format!("config {}: {e}", path.display()))
+            })?;
+            let file = Self::parse(&content).map_err(|e| {
+                CustomError::InvalidConfig(format!("config {}: {e}", path.display()))
+            })?;
+            config = config.merge(file);
+        }
+        Ok(config)
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(content).map_err(|e| e.to_string())?;
+        config
+            .defaults
+            .validate()
+            .map_err(|e| format!("[defaults]: {e}"))?;
+        for (name, settings) in &config.devices {
+            settings
+                .validate()
+                .map_err(|e| format!("[devices.{name}]: {e}"))?;
+        }
+        Ok(config)
+    }
+
+    /// Returns this configuration with the defaults and devices of `other` merged in.
+    pub fn merge(self, other: Config) -> Config {
+        let mut devices = self.devices;
+        for (name, settings) in other.devices {
+            let merged = devices.remove(&name).unwrap_or_default().merge(settings);
+            devices.insert(name, merged);
+        }
+        Config {
+            defaults: self.defaults.merge(other.defaults),
+            devices,
+        }
+    }
+
+    /// Returns the settings of the device `name` merged over the defaults.
+    pub fn device(&self, name: &str) -> Result<Settings> {
+        let settings = self.devices.get(name).ok_or_else(|| {
+            CustomError::InvalidConfig(format!(
+                "unknown device '{name}', configured: {}",
+                self.devices.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+        Ok(self.defaults.clone().merge(settings.clone()))
+    }
+}
+
+/// Returns the directory of the user's configuration,
+/// `$XDG_CONFIG_HOME/daylight-extender` or `~/.config/daylight-extender`.
+pub fn user_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("daylight-extender"))
+}
+
+/// Returns the user's configuration file, `<user_dir>/config.toml`.
+pub fn user_path() -> Option<PathBuf> {
+    user_dir().map(|dir| dir.join("config.toml"))
+}
+
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn instance<'de, D>(deserializer: D) -> Result<Option<KeyNamespace>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    KeyNamespace::new(&String::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn time_of_day<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&s, "%H:%M")
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("invalid time '{s}', expected HH:MM")))
+}
+
+fn hours<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let hours = u8::deserialize(deserializer)?;
+    if hours > 24 {
+        return Err(serde::de::Error::custom(format!(
+            "{hours} is not between 0 and 24"
+        )));
+    }
+    Ok(Some(hours))
+}
+
+fn percent<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let percent = u8::deserialize(deserializer)?;
+    if !(1..=100).contains(&percent) {
+        return Err(serde::de::Error::custom(format!(
+            "{percent} is not between 1 and 100"
+        )));
+    }
+    Ok(Some(percent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const CONFIG: &str = r#"
+[defaults]
+total_day_length = 16
+mode = "split"
+min_power = 40.0
+
+[devices.barn-1]
+host = "192.168.0.232"
+password = "secret"
+
+[devices.barn-2]
+host = "192.168.0.233"
+profile = "layers-16h"
+mode = "evening"
+"#;
+
+    #[test]
+    fn device_overrides_defaults() {
+        // arrange
+        let config = Config::parse(CONFIG).expect("Unexpected");
+
+        // act
+        let actual = config.device("barn-2");
+
+        // assert
+        let settings = actual.expect("Unexpected");
+        assert_eq!(Some("192.168.0.233".to_string()), settings.host);
+        assert_eq!(Some(ExtensionMode::Evening), settings.mode);
+        assert_eq!(Some(40.0), settings.min_power);
+        assert_eq!(Some("layers-16h".to_string()), settings.profile);
+        assert_eq!(None, settings.total_day_length);
+    }
+
+    #[test]
+    fn device_replaces_excluded_defaults() {
+        // arrange
+        let config = Config::parse(
+            r#"
+[defaults]
+profile = "layers-16h"
+night_break = 15
+
+[devices.barn-1]
+total_day_length = 14
+mode = "evening"
+"#,
+        )
+        .expect("Unexpected");
+
+        // act
+        let actual = config.device("barn-1");
+
+        // assert
+        let settings = actual.expect("Unexpected");
+        assert_eq!(Some(14), settings.total_day_length);
+        assert_eq!(None, settings.profile);
+        assert_eq!(Some(ExtensionMode::Evening), settings.mode);
+        assert_eq!(None, settings.night_break);
+        assert_eq!(Ok(()), settings.validate());
+    }
+
+    #[test]
+    fn device_unknown() {
+        // arrange
+        let config = Config::parse(CONFIG).expect("Unexpected");
+
+        // act
+        let result = config.device("barn-3");
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn merge_user_over_system() {
+        // arrange
+        let system = Config::parse(CONFIG).expect("Unexpected");
+        let user = Config::parse("[devices.barn-1]\nswitch_id = 1").expect("Unexpected");
+
+        // act
+        let actual = system.merge(user).device("barn-1");
+
+        // assert
+        let settings = actual.expect("Unexpected");
+        assert_eq!(Some(1), settings.switch_id);
+        assert_eq!(Some("secret".to_string()), settings.password);
+    }
+
+    #[rstest]
+    #[case("[defaults]\nphotoperiod = 16", "line 2")]
+    #[case("[defaults]\nmode = \"split\"\ntotal_day_length = 25", "line 3")]
+    #[case(
+        "[devices.barn-1]\nhost = \"192.168.0.232\"\ndimmer = \"120\"",
+        "line 3"
+    )]
+    #[case("[defaults]\nearliest_on = \"6am\"", "line 2")]
+    #[case("[defaults]\ninstance = \"barn 1\"", "line 2")]
+    #[case(
+        "[defaults]\nmode = \"split\"\nnight_break = 60",
+        "mode and night_break"
+    )]
+    #[case("[defaults]\ncheck_interval = 0", "key check_interval 0")]
+    #[case("[devices.barn-1]\nnight_break = 0", "key night_break 0")]
+    #[case(
+        "[devices.barn-1]\nprofile = \"layers-16h\"\ntotal_day_length = 16",
+        "[devices.barn-1]"
+    )]
+    fn parse_invalid(#[case] content: &str, #[case] expected: &str) {
+        // act
+        let result = Config::parse(content);
+
+        // assert
+        let e = result.expect_err("Expected Error is Ok");
+        assert!(e.contains(expected), "'{expected}' not in '{e}'");
+    }
+}
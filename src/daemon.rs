@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use log::{error, info, warn};
+
+use crate::accounting::{Ledger, Sample};
+use crate::error::CustomError;
+use crate::lamp::LampThreshold;
+use crate::program::DayLength;
+use crate::Controller;
+
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    /// Time between two checks of the switch state.
+    pub check_interval: Duration,
+    /// Verify the lamp's consumption against this threshold, if set.
+    pub lamp_threshold: Option<LampThreshold>,
+    /// Time after the scheduled on-time before the lamp is verified.
+    pub verify_delay: Duration,
+    /// Record on-time and energy of the switch in the ledger, if set.
+    pub accounting: Option<Accounting>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Accounting {
+    /// Path of the ledger file.
+    pub ledger_path: PathBuf,
+    /// Name of the channel in the ledger, e.g. `<host>/switch:<id>`.
+    pub channel: String,
+}
+
+/// Keeps the controller running: the schedule is refreshed once per day and the
+/// switch state is checked against the lighting window every `check_interval`.
+/// Failures are logged and retried on the next tick; the daemon only stops
+/// with a [`CustomError::LampFault`] when a lamp fails its verification.
+pub async fn run(
+    controller: &Controller<'_>,
+    day_length: &DayLength,
+    options: &DaemonOptions,
+) -> Result<()> {
+    let mut ledger = match &options.accounting {
+        Some(accounting) => Some(Ledger::load(&accounting.ledger_path)?),
+        None => None,
+    };
+    let mut scheduled_on: Option<NaiveDate> = None;
+    let mut verified_on: Option<NaiveDate> = None;
+    // Dropped once the output turns out not to report power.
+    let mut lamp_threshold = options.lamp_threshold.clone();
+    let mut interval = tokio::time::interval(options.check_interval);
+    loop {
+        interval.tick().await;
+
+        let today = Local::now().date_naive();
+        if scheduled_on != Some(today) {
+            match controller.execute(day_length).await {
+                Ok(revision) => {
+                    info!("Schedule (Rev: {revision}) to extend day length created or updated");
+                    scheduled_on = Some(today);
+                }
+                Err(e) => warn!("Failed to create or update the schedule: {e}"),
+            }
+        }
+
+        if let Err(e) = controller.catch_up(day_length).await {
+            warn!("Failed to check the switch state: {e}");
+        }
+
+        if let (Some(accounting), Some(ledger)) = (&options.accounting, ledger.as_mut()) {
+            if let Err(e) = record_sample(controller, day_length, options, accounting, ledger).await
+            {
+                warn!("Failed to record the switch's on-time and energy: {e}");
+            }
+        }
+
+        let Some(threshold) = &lamp_threshold else {
+            continue;
+        };
+        if verified_on == Some(today) {
+            continue;
+        }
+
+        let verify_delay = options.verify_delay.as_secs() as i64;
+        match controller.lighting_windows(day_length).await {
+            Ok((now, windows))
+                if windows.iter().any(|(light_on, light_off)| {
+                    now >= light_on + verify_delay && now < *light_off
+                }) =>
+            {
+                match controller.verify_lamp(threshold).await {
+                    Ok(true) => {
+                        info!("Lamp verified");
+                        verified_on = Some(today);
+                    }
+                    Ok(false) => {}
+                    Err(e)
+                        if matches!(
+                            e.downcast_ref::<CustomError>(),
+                            Some(CustomError::LampFault(_))
+                        ) =>
+                    {
+                        error!("ALERT: {e}");
+                        return Err(e);
+                    }
+                    Err(e)
+                        if matches!(
+                            e.downcast_ref::<CustomError>(),
+                            Some(CustomError::UnsupportedDevice(_))
+                        ) =>
+                    {
+                        warn!("{e}");
+                        lamp_threshold = None;
+                    }
+                    Err(e) => warn!("Failed to verify the lamp: {e}"),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to get the lighting windows: {e}"),
+        }
+    }
+}
+
+async fn record_sample(
+    controller: &Controller<'_>,
+    day_length: &DayLength,
+    options: &DaemonOptions,
+    accounting: &Accounting,
+    ledger: &mut Ledger,
+) -> Result<()> {
+    let (now, windows) = controller.recent_lighting_windows(day_length).await?;
+    let status = controller.output_status().await?;
+    let sample = Sample {
+        timestamp: now,
+        output: status.output,
+        energy_total: status.energy_total,
+    };
+    let max_gap = 2 * options.check_interval.as_secs() as i64;
+    ledger.record(&accounting.channel, sample, &windows, max_gap);
+    ledger.save(&accounting.ledger_path)
+}
@@ -0,0 +1,343 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::debug;
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+use shelly::api::{Gen1DeviceClient, Gen2DeviceClient};
+use shelly::data::{DeviceInfo, Gen1ShellyInfo};
+
+use crate::fleet;
+
+/// mDNS service types announced by Shelly devices.
+pub const SERVICE_TYPES: [&str; 2] = ["_shelly._tcp.local.", "_http._tcp.local."];
+
+/// Number of devices identified at the same time.
+const PARALLEL: usize = 16;
+/// Number of hosts probed at the same time when scanning a network.
+const SCAN_PARALLEL: usize = 64;
+/// Smallest prefix length of a network to scan, larger networks take too long.
+const MIN_SCAN_PREFIX: u8 = 16;
+
+/// A Shelly device found on the local network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceDescriptor {
+    /// Address to pass as `--host`, e.g. `192.168.0.232` or `192.168.0.232:8080`.
+    pub address: String,
+    pub id: String,
+    pub name: Option<String>,
+    pub model: String,
+    pub generation: u8,
+    pub mac: String,
+    pub firmware: String,
+}
+
+impl DeviceDescriptor {
+    pub fn new(address: &str, info: DeviceInfo) -> Self {
+        Self {
+            address: address.to_string(),
+            id: info.id,
+            name: info.name,
+            model: info.model,
+            generation: info.generation,
+            mac: info.mac,
+            firmware: info.ver,
+        }
+    }
+
+    /// Describes a Gen1 device, which has no device id, by its MAC address instead.
+    pub fn from_gen1(address: &str, info: Gen1ShellyInfo) -> Self {
+        Self {
+            address: address.to_string(),
+            id: info.mac.to_lowercase(),
+            name: None,
+            model: info.model.unwrap_or_default(),
+            generation: 1,
+            mac: info.mac,
+            firmware: info.fw.unwrap_or_default(),
+        }
+    }
+}
+
+/// An IPv4 network to scan, e.g. `192.168.0.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Network {
+    address: Ipv4Addr,
+    prefix: u8,
+}
+
+impl Ipv4Network {
+    /// Returns the host addresses of the network, leaving out the network and broadcast
+    /// addresses of networks larger than /31.
+    pub fn hosts(&self) -> Vec<Ipv4Addr> {
+        let mask = u32::MAX
+            .checked_shl(32 - u32::from(self.prefix))
+            .unwrap_or(0);
+        let network = u32::from(self.address) & mask;
+        let broadcast = network | !mask;
+        let range = if self.prefix >= 31 {
+            network..=broadcast
+        } else {
+            network + 1..=broadcast - 1
+        };
+        range.map(Ipv4Addr::from).collect()
+    }
+}
+
+impl FromStr for Ipv4Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = s.split_once('/').unwrap_or((s, "32"));
+        let address = address
+            .trim()
+            .parse::<Ipv4Addr>()
+            .map_err(|_| format!("invalid network '{s}', expected e.g. 192.168.0.0/24"))?;
+        let prefix = prefix
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| (MIN_SCAN_PREFIX..=32).contains(prefix))
+            .ok_or_else(|| {
+                format!("invalid prefix length in '{s}', expected {MIN_SCAN_PREFIX} -- 32")
+            })?;
+        Ok(Self { address, prefix })
+    }
+}
+
+/// Browses the local network for `timeout` and returns the Shelly devices that
+/// answered within `probe_timeout` each, ordered by address.
+pub async fn discover(timeout: Duration, probe_timeout: Duration) -> Result<Vec<DeviceDescriptor>> {
+    let daemon = ServiceDaemon::new()?;
+    let devices = discover_with(&daemon, timeout, probe_timeout).await;
+    let _ = daemon.shutdown();
+    devices
+}
+
+/// Like [`discover`], browsing with the given mDNS `daemon`.
+pub async fn discover_with(
+    daemon: &ServiceDaemon,
+    timeout: Duration,
+    probe_timeout: Duration,
+) -> Result<Vec<DeviceDescriptor>> {
+    let addresses = browse(daemon, &SERVICE_TYPES, timeout).await?;
+    Ok(describe_all(addresses.into_iter().collect(), PARALLEL, probe_timeout).await)
+}
+
+/// Probes every host of `network` on `port`, for networks where multicast is blocked.
+/// Hosts not answering within `timeout` are skipped.
+pub async fn scan(network: Ipv4Network, port: u16, timeout: Duration) -> Vec<DeviceDescriptor> {
+    let addresses = network
+        .hosts()
+        .into_iter()
+        .map(|ip| address(IpAddr::V4(ip), port))
+        .collect();
+    describe_all(addresses, SCAN_PARALLEL, timeout).await
+}
+
+/// Identifies the devices at `addresses`, at most `parallel` at a time, leaving out
+/// the ones that are not Shelly devices or do not answer within `timeout`.
+async fn describe_all(
+    addresses: Vec<String>,
+    parallel: usize,
+    timeout: Duration,
+) -> Vec<DeviceDescriptor> {
+    let devices = fleet::run_all(addresses, parallel, |address| async move {
+        let result = tokio::time::timeout(timeout, describe(&address))
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out")));
+        match result {
+            Ok(device) => Some(device),
+            Err(e) => {
+                debug!("{address} is not a Shelly device: {e}");
+                None
+            }
+        }
+    })
+    .await;
+    devices.into_iter().flatten().collect()
+}
+
+/// Identifies the device at `address` with `Shelly.GetDeviceInfo`, or with `GET /shelly`
+/// if it is not a Gen2 device.
+pub async fn describe(address: &str) -> Result<DeviceDescriptor> {
+    let gen2_error = match Gen2DeviceClient::new(address).get_device_info().await {
+        Ok(info) => return Ok(DeviceDescriptor::new(address, info)),
+        Err(e) => e,
+    };
+    match Gen1DeviceClient::new(address).get_shelly().await {
+        Ok(info) if info.generation() == 1 => Ok(DeviceDescriptor::from_gen1(address, info)),
+        _ => Err(gen2_error.into()),
+    }
+}
+
+/// Returns the addresses of the services of the given types resolved within `timeout`.
+async fn browse(
+    daemon: &ServiceDaemon,
+    service_types: &[&str],
+    timeout: Duration,
+) -> Result<BTreeSet<String>> {
+    let receivers = service_types
+        .iter()
+        .map(|service_type| daemon.browse(service_type))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut addresses = BTreeSet::new();
+    let deadline = tokio::time::Instant::now() + timeout;
+    while tokio::time::Instant::now() < deadline {
+        for receiver in &receivers {
+            while let Ok(event) = receiver.try_recv() {
+                let ServiceEvent::ServiceResolved(service) = event else {
+                    continue;
+                };
+                debug!("Resolved {}", service.get_fullname());
+                let ip = service
+                    .get_addresses()
+                    .iter()
+                    .map(|ip| ip.to_ip_addr())
+                    .min_by_key(IpAddr::is_ipv6);
+                if let Some(ip) = ip {
+                    addresses.insert(address(ip, service.get_port()));
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    for service_type in service_types {
+        let _ = daemon.stop_browse(service_type);
+    }
+    Ok(addresses)
+}
+
+/// Returns the address of a device for the client, leaving out the default HTTP port.
+fn address(ip: IpAddr, port: u16) -> String {
+    match (ip, port) {
+        (IpAddr::V4(ip), 80) => ip.to_string(),
+        (IpAddr::V6(ip), 80) => format!("[{ip}]"),
+        (IpAddr::V4(ip), port) => format!("{ip}:{port}"),
+        (IpAddr::V6(ip), port) => format!("[{ip}]:{port}"),
+    }
+}
+
+/// Returns the devices as a table.
+pub fn table(devices: &[DeviceDescriptor]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<22} {:<16} {:>3} {:<12} {:<14} Id",
+        "Address", "Model", "Gen", "MAC", "Firmware"
+    );
+    for device in devices {
+        let _ = writeln!(
+            out,
+            "{:<22} {:<16} {:>3} {:<12} {:<14} {}",
+            device.address, device.model, device.generation, device.mac, device.firmware, device.id
+        );
+    }
+    out
+}
+
+/// Returns the devices as `[devices.<id>]` tables of a configuration file.
+pub fn inventory(devices: &[DeviceDescriptor]) -> String {
+    let mut out = String::new();
+    for device in devices {
+        if let Some(name) = &device.name {
+            let _ = writeln!(out, "# {name}");
+        }
+        let _ = writeln!(out, "[devices.{}]", device.id);
+        let _ = writeln!(out, "host = \"{}\"", device.address);
+        let _ = writeln!(out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::net::Ipv6Addr;
+
+    #[rstest]
+    #[case(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 232)), 80, "192.168.0.232")]
+    #[case(
+        IpAddr::V4(Ipv4Addr::new(192, 168, 0, 232)),
+        8080,
+        "192.168.0.232:8080"
+    )]
+    #[case(IpAddr::V6(Ipv6Addr::LOCALHOST), 80, "[::1]")]
+    fn address_parametrized(#[case] ip: IpAddr, #[case] port: u16, #[case] expected: &str) {
+        // act
+        let actual = address(ip, port);
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+
+    #[rstest]
+    #[case("192.168.0.0/30", vec![Ipv4Addr::new(192, 168, 0, 1), Ipv4Addr::new(192, 168, 0, 2)])]
+    #[case("192.168.0.77/30", vec![Ipv4Addr::new(192, 168, 0, 77), Ipv4Addr::new(192, 168, 0, 78)])]
+    #[case("127.0.0.1", vec![Ipv4Addr::new(127, 0, 0, 1)])]
+    fn hosts_parametrized(#[case] network: &str, #[case] expected: Vec<Ipv4Addr>) {
+        // arrange
+        let network: Ipv4Network = network.parse().expect("Unexpected");
+
+        // act
+        let actual = network.hosts();
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn hosts_slash_24() {
+        // arrange
+        let network: Ipv4Network = "10.0.7.0/24".parse().expect("Unexpected");
+
+        // act
+        let actual = network.hosts();
+
+        // assert
+        assert_eq!(254, actual.len());
+        assert_eq!(Ipv4Addr::new(10, 0, 7, 254), actual[253]);
+    }
+
+    #[rstest]
+    #[case("192.168.0.0/8")]
+    #[case("192.168.0.0/33")]
+    #[case("192.168.0/24")]
+    fn network_from_str_invalid(#[case] s: &str) {
+        // act
+        let result = s.parse::<Ipv4Network>();
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn inventory_parses_as_config() {
+        // arrange
+        let devices = vec![DeviceDescriptor {
+            address: "192.168.0.232".to_string(),
+            id: "shellyplus1pm-a8032abe54dc".to_string(),
+            name: Some("barn-1".to_string()),
+            model: "SNSW-001P16EU".to_string(),
+            generation: 2,
+            mac: "A8032ABE54DC".to_string(),
+            firmware: "1.0.8".to_string(),
+        }];
+
+        // act
+        let actual = inventory(&devices);
+
+        // assert
+        let config: crate::config::Config = toml::from_str(&actual).expect("Unexpected");
+        assert_eq!(
+            Some("192.168.0.232".to_string()),
+            config.devices["shellyplus1pm-a8032abe54dc"].host
+        );
+    }
+}
@@ -3,6 +3,13 @@ use std::{error::Error, fmt};
 #[derive(Debug)]
 pub enum CustomError<'a> {
     ChronoError(&'a str),
+    LampFault(String),
+    PlacementError(String),
+    InvalidProfile(String),
+    InvalidProgram(String),
+    InvalidConfig(String),
+    UnsupportedDevice(String),
+    ScriptDeployed(String),
 }
 
 impl<'a> Error for CustomError<'a> {}
@@ -11,6 +18,13 @@ impl<'a> fmt::Display for CustomError<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CustomError::ChronoError(msg) => write!(f, "{msg}"),
+            CustomError::LampFault(msg) => write!(f, "{msg}"),
+            CustomError::PlacementError(msg) => write!(f, "{msg}"),
+            CustomError::InvalidProfile(msg) => write!(f, "{msg}"),
+            CustomError::InvalidProgram(msg) => write!(f, "{msg}"),
+            CustomError::InvalidConfig(msg) => write!(f, "{msg}"),
+            CustomError::UnsupportedDevice(msg) => write!(f, "{msg}"),
+            CustomError::ScriptDeployed(msg) => write!(f, "{msg}"),
         }
     }
 }
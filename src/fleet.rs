@@ -0,0 +1,174 @@
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Arc;
+
+use chrono::{Local, LocalResult, TimeZone};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// The schedule revision, which Gen1 devices do not have, and today's lighting windows.
+pub type Scheduled = (Option<u32>, Vec<(i64, i64)>);
+
+/// Outcome of scheduling one device of the fleet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceResult {
+    pub device: String,
+    pub host: String,
+    /// The schedule revision and today's lighting windows, or the error.
+    pub outcome: Result<Scheduled, String>,
+}
+
+/// Runs `task` for every device with at most `parallel` devices at a time and returns
+/// the results in the order of `devices`.
+pub async fn run_all<D, F, Fut, T>(devices: Vec<D>, parallel: usize, task: F) -> Vec<T>
+where
+    D: Send + 'static,
+    F: Fn(D) -> Fut,
+    Fut: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(parallel.max(1)));
+    let mut set = JoinSet::new();
+    for (i, device) in devices.into_iter().enumerate() {
+        let semaphore = semaphore.clone();
+        let future = task(device);
+        set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            (i, future.await)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(result) = set.join_next().await {
+        match result {
+            Ok(result) => results.push(result),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Returns the summary table of the fleet run.
+pub fn summary(results: &[DeviceResult]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<20} {:<20} {:>6} {:<26} Error",
+        "Device", "Host", "Rev", "Action"
+    );
+    for result in results {
+        let (rev, action, error) = match &result.outcome {
+            Ok((rev, windows)) => (
+                rev.map_or("-".to_string(), |rev| rev.to_string()),
+                action(windows),
+                String::new(),
+            ),
+            Err(e) => ("-".to_string(), "-".to_string(), e.clone()),
+        };
+        let row = format!(
+            "{:<20} {:<20} {:>6} {:<26} {}",
+            result.device, result.host, rev, action, error
+        );
+        let _ = writeln!(out, "{}", row.trim_end());
+    }
+    out
+}
+
+/// Describes the scheduled windows, e.g. `on 05:30-08:14`.
+fn action(windows: &[(i64, i64)]) -> String {
+    if windows.is_empty() {
+        return "off, no light needed".to_string();
+    }
+
+    let time = |timestamp: i64| match Local.timestamp_opt(timestamp, 0) {
+        LocalResult::Single(dt) => dt.format("%H:%M").to_string(),
+        _ => "?".to_string(),
+    };
+    let windows: Vec<String> = windows
+        .iter()
+        .map(|(start, end)| format!("{}-{}", time(*start), time(*end)))
+        .collect();
+    format!("on {}", windows.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn run_all_bounded() {
+        // arrange
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        // act
+        let results = run_all((0..10).collect(), 3, |i: usize| {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10 * (10 - i as u64))).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                i * 2
+            }
+        })
+        .await;
+
+        // assert
+        assert_eq!((0..10).map(|i| i * 2).collect::<Vec<_>>(), results);
+        assert_eq!(3, max_running.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn summary_table() {
+        // arrange
+        // Wednesday, 20 December 2023 06:00 -- 08:14:19 in Berlin, shown in local time
+        let results = vec![
+            DeviceResult {
+                device: "barn-1".to_string(),
+                host: "192.168.0.232".to_string(),
+                outcome: Ok((Some(42), vec![(1703048400, 1703056459)])),
+            },
+            DeviceResult {
+                device: "barn-2".to_string(),
+                host: "192.168.0.233".to_string(),
+                outcome: Ok((Some(7), vec![])),
+            },
+            DeviceResult {
+                device: "barn-3".to_string(),
+                host: "192.168.0.234".to_string(),
+                outcome: Ok((None, vec![(1703048400, 1703056459)])),
+            },
+            DeviceResult {
+                device: "barn-4".to_string(),
+                host: "192.168.0.235".to_string(),
+                outcome: Err("connection refused".to_string()),
+            },
+        ];
+
+        // act
+        let actual = summary(&results);
+
+        // assert
+        let hhmm = |timestamp| {
+            Local
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .expect("Unexpected")
+                .format("%H:%M")
+                .to_string()
+        };
+        let window = format!("{}-{}", hhmm(1703048400), hhmm(1703056459));
+        let lines: Vec<&str> = actual.lines().collect();
+        assert_eq!(5, lines.len());
+        assert!(lines[1].starts_with("barn-1"));
+        assert!(lines[1].contains(&format!("42 on {window}")));
+        assert!(lines[2].contains("7 off, no light needed"));
+        assert!(lines[3].contains(&format!(" - on {window}")));
+        assert!(lines[4].ends_with(" connection refused"));
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::Result;
+use chrono::{Local, LocalResult, TimeZone};
+use log::{debug, info, trace};
+use shelly::api::Gen1DeviceClient;
+use shelly::data::Gen1ShellyInfo;
+use shelly::error::ShellyRpcError;
+
+use crate::error::CustomError;
+use crate::placement::Placement;
+use crate::program::DayLength;
+use crate::{Controller, DEFAULT_MIN_EXTENSION};
+
+/// Days of the week a schedule rule applies on, Monday (0) to Sunday (6).
+const EVERY_DAY: &str = "0123456";
+
+/// Controller of a Gen1 relay, e.g. Shelly 1 or Shelly 1PM.
+///
+/// Gen1 devices have neither the Schedule service nor a KVS, so the lighting windows
+/// are written as the relay's schedule rules, one `on` and one `off` rule per window,
+/// e.g. `0600-0123456-on` and `0814-0123456-off`. The rules replace the relay's
+/// schedule, which is therefore owned by the controller. Rules have a resolution of
+/// one minute.
+#[derive(Debug)]
+pub struct Gen1Controller<'a> {
+    client: &'a Gen1DeviceClient<'a>,
+    switch_id: u8,
+    placement: Placement,
+    min_extension: i64,
+}
+
+impl<'a> Gen1Controller<'a> {
+    pub fn new(client: &'a Gen1DeviceClient<'a>) -> Self {
+        Self {
+            client,
+            switch_id: 0,
+            placement: Placement::default(),
+            min_extension: DEFAULT_MIN_EXTENSION,
+        }
+    }
+
+    /// Selects the relay (`/relay/<id>`) that is scheduled, defaults to 0.
+    pub fn with_switch_id(mut self, switch_id: u8) -> Self {
+        self.switch_id = switch_id;
+        self
+    }
+
+    /// Decides whether the light is placed before sunrise, after sunset or both,
+    /// defaults to before sunrise.
+    pub fn with_placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Skips days on which the light would be on for less than `min_extension` seconds,
+    /// defaults to [`DEFAULT_MIN_EXTENSION`].
+    pub fn with_min_extension(mut self, min_extension: i64) -> Self {
+        self.min_extension = min_extension;
+        self
+    }
+
+    /// Writes today's lighting windows as the relay's schedule rules and returns them
+    /// as `(light_on, light_off)`. The rules are left alone if they are up to date.
+    pub async fn execute(&self, day_length: &DayLength) -> Result<Vec<(i64, i64)>> {
+        let (_, windows) = self.lighting_windows(day_length).await?;
+        let rules = Self::schedule_rules(&windows)?;
+
+        let enable = !rules.is_empty();
+        let settings = self.client.get_relay_settings(self.switch_id).await?;
+        if settings.schedule_rules == rules && settings.schedule == enable {
+            debug!("Schedule rules of relay {} are up to date", self.switch_id);
+            return Ok(windows);
+        }
+        if !settings.schedule_rules.is_empty() {
+            info!(
+                "Replacing schedule rules {} of relay {}",
+                settings.schedule_rules.join(","),
+                self.switch_id
+            );
+        }
+        self.client
+            .set_schedule_rules(self.switch_id, &rules)
+            .await?;
+        Ok(windows)
+    }
+
+    /// Returns the device time and today's lighting windows as `(light_on, light_off)`,
+    /// which are empty if no supplemental light is needed today.
+    pub async fn lighting_windows(&self, day_length: &DayLength) -> Result<(i64, Vec<(i64, i64)>)> {
+        trace!("lighting_windows");
+        let (latitude, longitude) = self.client.get_location().await?;
+        let now = self.client.get_time().await?;
+        let (sunrise, sunset) = Controller::sunrise_sunset_at(latitude, longitude, now)?;
+        let day_length_seconds = Controller::day_length_seconds(day_length, now)?;
+        let windows =
+            self.placement
+                .windows(sunrise, sunset, day_length_seconds, self.min_extension)?;
+        Ok((now, windows))
+    }
+
+    /// Checks that the device is a Gen1 device with the controlled relay, so that it is
+    /// not scheduled with calls it cannot execute. Returns the device's identification.
+    pub async fn check_device(&self) -> Result<Gen1ShellyInfo> {
+        let info = self.client.get_shelly().await?;
+        let model = info.model.clone().unwrap_or_default();
+        if info.generation() != 1 {
+            return Err(CustomError::UnsupportedDevice(format!(
+                "{model} is a Gen{} device, use the Gen2 RPC API",
+                info.generation()
+            ))
+            .into());
+        }
+
+        match self.client.get_relay_status(self.switch_id).await {
+            Ok(_) => Ok(info),
+            Err(ShellyRpcError::HttpStatusError(404, _)) => Err(CustomError::UnsupportedDevice(
+                format!("{model} has no relay {}", self.switch_id),
+            )
+            .into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the schedule rules switching the relay on and off for the given windows.
+    pub fn schedule_rules(windows: &[(i64, i64)]) -> Result<Vec<String>> {
+        let mut rules = Vec::new();
+        for (light_on, light_off) in windows {
+            rules.push(Self::rule(*light_on, "on")?);
+            rules.push(Self::rule(*light_off, "off")?);
+        }
+        Ok(rules)
+    }
+
+    fn rule(timestamp: i64, action: &str) -> Result<String> {
+        if let LocalResult::Single(dt) = Local.timestamp_opt(timestamp, 0) {
+            Ok(format!("{}-{EVERY_DAY}-{action}", dt.format("%H%M")))
+        } else {
+            Err(CustomError::ChronoError("timestamp out of range").into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    /// Local time of day as `(hour, minute, second)`.
+    type TimeOfDay = (u32, u32, u32);
+
+    #[rstest]
+    // Wednesday, 20 December 2023 06:00:00 -- 08:14:19
+    #[case(vec![((6, 0, 0), (8, 14, 19))], vec!["0600-0123456-on", "0814-0123456-off"])]
+    // and 15:53:24 -- 18:05:00
+    #[case(
+        vec![((6, 0, 0), (8, 14, 19)), ((15, 53, 24), (18, 5, 0))],
+        vec!["0600-0123456-on", "0814-0123456-off", "1553-0123456-on", "1805-0123456-off"]
+    )]
+    #[case(vec![], vec![])]
+    fn schedule_rules_parametrized(
+        #[case] local_windows: Vec<(TimeOfDay, TimeOfDay)>,
+        #[case] expected: Vec<&str>,
+    ) {
+        // arrange
+        let timestamp = |(hour, min, sec)| {
+            Local
+                .with_ymd_and_hms(2023, 12, 20, hour, min, sec)
+                .single()
+                .expect("Unexpected")
+                .timestamp()
+        };
+        let windows: Vec<(i64, i64)> = local_windows
+            .into_iter()
+            .map(|(on, off)| (timestamp(on), timestamp(off)))
+            .collect();
+
+        // act
+        let actual = Gen1Controller::schedule_rules(&windows);
+
+        // assert
+        assert_eq!(expected, actual.expect("Unexpected"));
+    }
+}
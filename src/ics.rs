@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+
+use crate::plan::LightingWindow;
+
+const PRODID: &str = "-//daylight_extender//lighting plan//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Renders the lighting windows as an RFC 5545 iCalendar document,
+/// one VEVENT per window, each titled with `summary`.
+pub fn to_ics(windows: &[LightingWindow], summary: &str, dtstamp: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{PRODID}"),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+    ];
+
+    let uid_name: String = summary
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let dtstamp = dtstamp.format("%Y%m%dT%H%M%SZ");
+    for window in windows {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!(
+            "UID:{}-{}@daylight_extender",
+            window.start, uid_name
+        ));
+        lines.push(format!("DTSTAMP:{dtstamp}"));
+        lines.push(format!("DTSTART:{}", format_utc(window.start)));
+        lines.push(format!("DTEND:{}", format_utc(window.end)));
+        lines.push(format!("SUMMARY:{}", escape_text(summary)));
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+fn format_utc(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+/// See: https://www.rfc-editor.org/rfc/rfc5545#section-3.3.11
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// See: https://www.rfc-editor.org/rfc/rfc5545#section-3.1
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn to_ics_one_event_per_window() {
+        // arrange
+        let windows = vec![
+            LightingWindow {
+                date: NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected"),
+                start: 1703040804,
+                end: 1703056459,
+            },
+            LightingWindow {
+                date: NaiveDate::from_ymd_opt(2023, 12, 21).expect("Unexpected"),
+                start: 1703127229,
+                end: 1703142892,
+            },
+        ];
+        let dtstamp = DateTime::from_timestamp(1703085600, 0).expect("Unexpected");
+
+        // act
+        let actual = to_ics(&windows, "Coop 1, switch:0", dtstamp);
+
+        // assert
+        assert!(actual.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(actual.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(2, actual.matches("BEGIN:VEVENT\r\n").count());
+        assert!(actual.contains("DTSTART:20231220T025324Z\r\n"));
+        assert!(actual.contains("DTEND:20231220T071419Z\r\n"));
+        assert!(actual.contains("DTSTAMP:20231220T152000Z\r\n"));
+        assert!(actual.contains("SUMMARY:Coop 1\\, switch:0\r\n"));
+    }
+
+    #[test]
+    fn fold_line_long_lines() {
+        // arrange
+        let line = format!("SUMMARY:{}", "x".repeat(100));
+
+        // act
+        let actual = fold_line(&line);
+
+        // assert
+        let parts: Vec<&str> = actual.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(2, parts.len());
+        assert_eq!(75, parts[0].len());
+        assert!(parts[1].starts_with(' '));
+        assert_eq!(line, format!("{}{}", parts[0], &parts[1][1..]));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::colour::MAX_CURVE_STEPS;
+use crate::error::CustomError;
+
+/// Prefix of every KVS key written by the controller.
+pub const KEY_PREFIX: &str = "dle";
+/// Name of the instance used when none is configured.
+pub const DEFAULT_INSTANCE: &str = "default";
+/// The device rejects keys that are 42 characters or longer.
+pub const MAX_KEY_LENGTH: usize = 41;
+
+/// Scopes the KVS keys of one controller instance, so that independent
+/// configurations on the same device do not overwrite each other's bookkeeping.
+/// Keys have the form `dle.<instance>.<switch_id>.<name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyNamespace {
+    instance: String,
+}
+
+impl KeyNamespace {
+    /// Returns the namespace of the given instance, refusing a name of which the
+    /// longest key, the last evening colour step of switch 255, would be too long.
+    pub fn new(instance: &str) -> Result<Self, CustomError<'static>> {
+        if instance.is_empty() {
+            return Err(CustomError::InvalidConfig(
+                "instance name must not be empty".to_string(),
+            ));
+        }
+
+        if !instance
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(CustomError::InvalidConfig(format!(
+                "instance name '{instance}' may only contain ASCII letters, digits, '-' and '_'"
+            )));
+        }
+
+        let namespace = Self {
+            instance: instance.to_string(),
+        };
+        let longest = namespace.evening_step_job_key(u8::MAX, MAX_CURVE_STEPS - 1);
+        if longest.len() > MAX_KEY_LENGTH {
+            return Err(CustomError::InvalidConfig(format!(
+                "instance name '{instance}' is too long, key '{longest}' exceeds {MAX_KEY_LENGTH} characters"
+            )));
+        }
+        Ok(namespace)
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    /// Returns the KVS key holding the schedule job id of the given switch.
+    pub fn job_key(&self, switch_id: u8) -> String {
+        self.key(switch_id, "job")
+    }
+
+    /// Returns the KVS key holding the id of the paired switch-off job of the given switch.
+    pub fn off_job_key(&self, switch_id: u8) -> String {
+        self.key(switch_id, "off")
+    }
+
+    /// Returns the KVS key holding the schedule job id of the evening window of the given switch.
+    pub fn evening_job_key(&self, switch_id: u8) -> String {
+        self.key(switch_id, "eve")
+    }
+
+    /// Returns the KVS key holding the id of the evening switch-off job of the given switch.
+    pub fn evening_off_job_key(&self, switch_id: u8) -> String {
+        self.key(switch_id, "eve.off")
+    }
+
+    /// Returns the KVS key holding the id of the colour step job `step` of the given switch.
+    pub fn step_job_key(&self, switch_id: u8, step: usize) -> String {
+        self.key(switch_id, &format!("s.{step}"))
+    }
+
+    /// Returns the KVS key holding the id of the evening colour step job `step` of the
+    /// given switch.
+    pub fn evening_step_job_key(&self, switch_id: u8, step: usize) -> String {
+        self.key(switch_id, &format!("eve.s.{step}"))
+    }
+
+    /// Returns the name of the on-device script of the given switch, e.g. `dle-default-0`.
+    pub fn script_name(&self, switch_id: u8) -> String {
+        format!("{KEY_PREFIX}-{}-{switch_id}", self.instance)
+    }
+
+    fn key(&self, switch_id: u8, name: &str) -> String {
+        format!("{KEY_PREFIX}.{}.{switch_id}.{name}", self.instance)
+    }
+}
+
+impl Default for KeyNamespace {
+    fn default() -> Self {
+        Self {
+            instance: DEFAULT_INSTANCE.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("default")]
+    #[case("coop-1")]
+    #[case("evening_job")]
+    fn new_valid(#[case] instance: &str) {
+        // act
+        let result = KeyNamespace::new(instance);
+
+        // assert
+        assert!(result.is_ok(), "Expected Ok is Error");
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("coop.1")]
+    #[case("coop 1")]
+    #[case("an-instance-name-which-is-far-too-long")]
+    fn new_invalid(#[case] instance: &str) {
+        // act
+        let result = KeyNamespace::new(instance);
+
+        // assert
+        assert!(
+            matches!(result, Err(CustomError::InvalidConfig(_))),
+            "Unexpected {result:?}"
+        );
+    }
+
+    #[test]
+    fn longest_key_fits() {
+        // arrange
+        let instance = "x".repeat(25);
+
+        // act
+        let namespace = KeyNamespace::new(&instance).expect("Unexpected");
+        let too_long = KeyNamespace::new(&format!("{instance}x"));
+
+        // assert
+        let key = namespace.evening_step_job_key(u8::MAX, MAX_CURVE_STEPS - 1);
+        assert_eq!(MAX_KEY_LENGTH, key.len());
+        assert!(too_long.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn job_key() {
+        // act
+        let key = KeyNamespace::default().job_key(3);
+
+        // assert
+        assert_eq!("dle.default.3.job", key);
+    }
+
+    #[test]
+    fn evening_off_job_key() {
+        // act
+        let key = KeyNamespace::default().evening_off_job_key(3);
+
+        // assert
+        assert_eq!("dle.default.3.eve.off", key);
+    }
+
+    #[test]
+    fn script_name() {
+        // act
+        let name = KeyNamespace::new("coop-1")
+            .expect("Unexpected")
+            .script_name(2);
+
+        // assert
+        assert_eq!("dle-coop-1-2", name);
+    }
+}
@@ -0,0 +1,55 @@
+/// Minimum consumption expected from a working lamp while its switch is on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LampThreshold {
+    /// Minimum active power in watts.
+    pub min_power: f64,
+    /// Minimum current in amperes, not checked if `None`.
+    pub min_current: Option<f64>,
+}
+
+impl LampThreshold {
+    /// Returns true if the measured `apower` and `current` meet the threshold.
+    pub fn is_met(&self, apower: f64, current: Option<f64>) -> bool {
+        if apower < self.min_power {
+            return false;
+        }
+
+        match (self.min_current, current) {
+            (Some(min_current), Some(current)) => current >= min_current,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(10.0, None, 8.9, Some(0.04), false)]
+    #[case(5.0, None, 8.9, Some(0.04), true)]
+    #[case(5.0, Some(0.05), 8.9, Some(0.04), false)]
+    #[case(5.0, Some(0.03), 8.9, Some(0.04), true)]
+    #[case(5.0, Some(0.03), 8.9, None, true)]
+    #[case(0.5, None, 0.0, Some(0.0), false)]
+    fn is_met_parametrized(
+        #[case] min_power: f64,
+        #[case] min_current: Option<f64>,
+        #[case] apower: f64,
+        #[case] current: Option<f64>,
+        #[case] expected: bool,
+    ) {
+        // arrange
+        let threshold = LampThreshold {
+            min_power,
+            min_current,
+        };
+
+        // act
+        let actual = threshold.is_met(apower, current);
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+}
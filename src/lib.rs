@@ -1,58 +1,650 @@
 use anyhow::Result;
 use chrono::{Datelike, Local, LocalResult, TimeZone, Timelike};
-use log::{trace, warn};
+use log::{debug, info, trace, warn};
 use shelly::api::Gen2DeviceClient;
-use shelly::data::{ScheduleJobMethod, ScheduleJobWithOptionalId, KEY_NOT_FOUND};
+use shelly::data::{DeviceInfo, ScheduleJobMethod, ScheduleJobWithOptionalId, KEY_NOT_FOUND};
 use shelly::error::ShellyRpcError;
 
+pub mod accounting;
+pub mod colour;
+pub mod config;
+pub mod daemon;
+pub mod discovery;
 pub mod error;
+pub mod fleet;
+pub mod gen1;
+pub mod ics;
+pub mod kvs;
+pub mod lamp;
+pub mod output;
+pub mod placement;
+pub mod plan;
+pub mod profile;
+pub mod program;
+pub mod script;
+pub mod tariff;
+use crate::colour::{Colour, MAX_CURVE_STEPS};
 use crate::error::CustomError;
+use crate::kvs::{KeyNamespace, DEFAULT_INSTANCE};
+use crate::lamp::LampThreshold;
+use crate::output::{Output, OutputStatus};
+use crate::placement::Placement;
+use crate::program::DayLength;
 
+/// Legacy KVS key of the schedule job id of switch 0, superseded by
+/// [`KeyNamespace::job_key`]. A job id found under it is migrated into the default
+/// instance's namespace.
 pub const SCHEDULE_JOB_ID: &str = "daylight.extender.job.id";
-const THIRTY_MINS_AS_SEC: i64 = 30 * 60;
+/// Extensions shorter than this many seconds are not worth switching the light for.
+pub const DEFAULT_MIN_EXTENSION: i64 = 30 * 60;
+
+/// The two daily slots a controller schedules, each with its own jobs and KVS keys.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    /// The window before sunrise.
+    Morning,
+    /// The window after sunset, or a night break.
+    Evening,
+}
+
+impl Slot {
+    fn job_key(self, namespace: &KeyNamespace, switch_id: u8) -> String {
+        match self {
+            Slot::Morning => namespace.job_key(switch_id),
+            Slot::Evening => namespace.evening_job_key(switch_id),
+        }
+    }
+
+    fn off_job_key(self, namespace: &KeyNamespace, switch_id: u8) -> String {
+        match self {
+            Slot::Morning => namespace.off_job_key(switch_id),
+            Slot::Evening => namespace.evening_off_job_key(switch_id),
+        }
+    }
+
+    fn step_job_key(self, namespace: &KeyNamespace, switch_id: u8, step: usize) -> String {
+        match self {
+            Slot::Morning => namespace.step_job_key(switch_id, step),
+            Slot::Evening => namespace.evening_step_job_key(switch_id, step),
+        }
+    }
+}
+
+/// How the times of schedule jobs are written.
+#[derive(Debug, Clone, Copy)]
+enum Timespecs {
+    /// Local clock times, moved along with the sun by the next run.
+    Clock,
+    /// Offsets from the sunrise (morning slot) and the sunset (evening slot) of the
+    /// day, which the device follows by itself.
+    SunRelative { sunrise: i64, sunset: i64 },
+}
+
+impl Timespecs {
+    fn timespec(self, slot: Slot, timestamp: i64) -> Result<String> {
+        match (self, slot) {
+            (Timespecs::Clock, _) => Controller::get_timespec(timestamp),
+            (Timespecs::SunRelative { sunrise, .. }, Slot::Morning) => {
+                Ok(Controller::get_sun_timespec("sunrise", timestamp - sunrise))
+            }
+            (Timespecs::SunRelative { sunset, .. }, Slot::Evening) => {
+                Ok(Controller::get_sun_timespec("sunset", timestamp - sunset))
+            }
+        }
+    }
+}
+
+/// Days over which the deviation of a sun-relative schedule is reported.
+const SUN_RELATIVE_REPORT_DAYS: [u32; 2] = [7, 30];
+
+/// RPC methods the controller calls, by service.
+const REQUIRED_METHODS: [(&str, &[&str]); 2] = [
+    (
+        "Schedule",
+        &["Schedule.Create", "Schedule.Update", "Schedule.List"],
+    ),
+    ("KVS", &["KVS.Get", "KVS.Set", "KVS.Delete"]),
+];
 
 #[derive(Debug)]
 pub struct Controller<'a> {
     client: &'a Gen2DeviceClient<'a>,
+    switch_id: u8,
+    namespace: KeyNamespace,
+    switch_off_job: bool,
+    placement: Placement,
+    output: Output,
+    min_extension: i64,
+    sun_relative: bool,
 }
 
 impl<'a> Controller<'a> {
     pub fn new(client: &'a Gen2DeviceClient<'a>) -> Self {
-        Self { client }
+        Self {
+            client,
+            switch_id: 0,
+            namespace: KeyNamespace::default(),
+            switch_off_job: false,
+            placement: Placement::default(),
+            output: Output::default(),
+            min_extension: DEFAULT_MIN_EXTENSION,
+            sun_relative: false,
+        }
+    }
+
+    /// Selects the output channel (`switch:<id>`) that is scheduled, defaults to 0.
+    pub fn with_switch_id(mut self, switch_id: u8) -> Self {
+        self.switch_id = switch_id;
+        self
     }
 
-    pub async fn execute(&self, day_length_hours: u8) -> Result<u32> {
-        let day_length_seconds = i64::from(day_length_hours) * 60 * 60;
+    /// Scopes the KVS keys written by this controller to the given instance.
+    pub fn with_namespace(mut self, namespace: KeyNamespace) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Switches the light off with a paired schedule job at the end of the window
+    /// instead of relying on `toggle_after`, so the relay recovers after a power loss.
+    pub fn with_switch_off_job(mut self, switch_off_job: bool) -> Self {
+        self.switch_off_job = switch_off_job;
+        self
+    }
+
+    /// Decides whether the light is placed before sunrise, after sunset or both,
+    /// defaults to before sunrise.
+    pub fn with_placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Skips days on which the light would be on for less than `min_extension` seconds,
+    /// defaults to [`DEFAULT_MIN_EXTENSION`].
+    pub fn with_min_extension(mut self, min_extension: i64) -> Self {
+        self.min_extension = min_extension;
+        self
+    }
 
-        let (sunrise, sunset) = self.get_sunrise_sunset().await?;
-        let (light_on, toggle_after) =
-            Self::light_on_toggle_after(sunrise, sunset, day_length_seconds)?;
-        self.create_or_update_schedule(light_on, toggle_after).await
+    /// Selects the component the light is connected to, defaults to a switch.
+    /// Dimmers and colour lights are always switched off with a paired schedule job.
+    pub fn with_output(mut self, output: Output) -> Self {
+        self.output = output;
+        self
     }
 
-    async fn get_sunrise_sunset(&self) -> Result<(i64, i64)> {
-        trace!("get_sunrise_sunset");
-        let (latitude, longitude) = self.client.get_location().await?;
+    /// Writes the job times relative to sunrise and sunset, e.g. `@sunrise-2h30m`, so that
+    /// the device moves the light along with the sun by itself. The extension stays the
+    /// same every day, so the lit day drifts from the day length until the next run.
+    /// Requires a fixed placement.
+    pub fn with_sun_relative(mut self, sun_relative: bool) -> Self {
+        self.sun_relative = sun_relative;
+        self
+    }
+
+    pub async fn execute(&self, day_length: &DayLength) -> Result<u32> {
+        Ok(self.execute_with_windows(day_length).await?.0)
+    }
+
+    /// Like [`Controller::execute`], also returning today's scheduled lighting windows
+    /// as `(light_on, light_off)`.
+    pub async fn execute_with_windows(
+        &self,
+        day_length: &DayLength,
+    ) -> Result<(u32, Vec<(i64, i64)>)> {
+        if self.sun_relative && !matches!(self.placement, Placement::Fixed(_)) {
+            return Err(CustomError::PlacementError(
+                "sun-relative schedules need a fixed mode, the placement changes daily".to_string(),
+            )
+            .into());
+        }
+        // The schedule jobs would drive the light next to the script. A device without
+        // the Script service, or failing to list its scripts, cannot run the script.
+        match self.find_script().await {
+            Ok(Some(script)) => {
+                return Err(CustomError::ScriptDeployed(format!(
+                    "script '{}' switches the light, remove it with `script --remove` first",
+                    script.name
+                ))
+                .into())
+            }
+            Ok(None) => {}
+            Err(e) => debug!("Cannot list the scripts, assuming none is deployed: {e}"),
+        }
+
+        let ((latitude, longitude), now) = self.get_location_time().await?;
+        let (sunrise, sunset) = Self::sunrise_sunset_at(latitude, longitude, now)?;
+        let day_length_seconds = Self::day_length_seconds(day_length, now)?;
+        let (morning, evening) =
+            self.placement
+                .slots(sunrise, sunset, day_length_seconds, self.min_extension)?;
+        let timespecs = if self.sun_relative {
+            Timespecs::SunRelative { sunrise, sunset }
+        } else {
+            Timespecs::Clock
+        };
+        let revision = self
+            .create_or_update_schedule(morning, evening, timespecs)
+            .await?;
+        let windows: Vec<(i64, i64)> = morning.into_iter().chain(evening).collect();
+
+        if self.sun_relative && !windows.is_empty() {
+            let LocalResult::Single(dt) = Local.timestamp_opt(now, 0) else {
+                return Err(CustomError::ChronoError("timestamp out of range").into());
+            };
+            let extension = windows.iter().map(|(start, end)| end - start).sum();
+            let deviations = SUN_RELATIVE_REPORT_DAYS
+                .iter()
+                .map(|days| {
+                    let deviation = plan::sun_relative_deviation(
+                        latitude,
+                        longitude,
+                        dt.date_naive(),
+                        *days,
+                        day_length,
+                        extension,
+                    )?;
+                    Ok(format!("{}m over {days} days", deviation / 60))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            info!(
+                "Sun-relative schedule extends the day by {}m, the lit day deviates from the day length by up to {}",
+                extension / 60,
+                deviations.join(" and ")
+            );
+        }
+        Ok((revision, windows))
+    }
+
+    /// Returns the device time and today's lighting windows as `(light_on, light_off)`,
+    /// which are empty if no supplemental light is needed today.
+    pub async fn lighting_windows(&self, day_length: &DayLength) -> Result<(i64, Vec<(i64, i64)>)> {
+        let (now, sunrise, sunset) = self.get_time_sunrise_sunset().await?;
+        let day_length_seconds = Self::day_length_seconds(day_length, now)?;
+        let windows =
+            self.placement
+                .windows(sunrise, sunset, day_length_seconds, self.min_extension)?;
+        Ok((now, windows))
+    }
+
+    /// Returns the device's current time and the lighting windows of yesterday and
+    /// today, as a night break or a late evening window of yesterday may run past midnight.
+    pub async fn recent_lighting_windows(
+        &self,
+        day_length: &DayLength,
+    ) -> Result<(i64, Vec<(i64, i64)>)> {
+        let ((latitude, longitude), now) = self.get_location_time().await?;
+        let mut windows = Vec::new();
+        for timestamp in [now - 24 * 60 * 60, now] {
+            let (sunrise, sunset) = Self::sunrise_sunset_at(latitude, longitude, timestamp)?;
+            let day_length_seconds = Self::day_length_seconds(day_length, timestamp)?;
+            windows.extend(self.placement.windows(
+                sunrise,
+                sunset,
+                day_length_seconds,
+                self.min_extension,
+            )?);
+        }
+        Ok((now, windows))
+    }
+
+    /// Switches the light on for the remainder of the current lighting window if the
+    /// device is inside the window but the output is off, e.g. after a power cut.
+    /// Returns the remaining duration in seconds if the light was switched on.
+    pub async fn catch_up(&self, day_length: &DayLength) -> Result<Option<i64>> {
+        let (now, windows) = self.recent_lighting_windows(day_length).await?;
+        let Some((light_on, light_off)) = Self::window_at(now, &windows) else {
+            return Ok(None);
+        };
+        let remaining = light_off - now;
+
+        let status = self.output_status().await?;
+        if status.output {
+            return Ok(None);
+        }
+
+        match &self.output {
+            Output::Switch => {
+                self.client
+                    .set_switch(self.switch_id, true, Some(remaining))
+                    .await?;
+            }
+            Output::Dimmer { brightness, .. } => {
+                self.client
+                    .set_light(self.switch_id, true, Some(*brightness), Some(remaining))
+                    .await?;
+            }
+            Output::Colour { brightness, curve } => {
+                let fraction = (now - light_on) as f64 / (light_off - light_on) as f64;
+                match curve.colour_at(fraction) {
+                    Colour::Temperature(ct) => {
+                        self.client
+                            .set_cct(
+                                self.switch_id,
+                                true,
+                                Some(*brightness),
+                                Some(ct),
+                                Some(remaining),
+                            )
+                            .await?;
+                    }
+                    Colour::Rgb(rgb) => {
+                        self.client
+                            .set_rgbw(
+                                self.switch_id,
+                                true,
+                                Some(*brightness),
+                                Some(rgb),
+                                Some(remaining),
+                            )
+                            .await?;
+                    }
+                }
+            }
+        }
+        info!(
+            "Catch-up: {} was off inside the lighting window, switched on for the remaining {remaining}s",
+            self.component()
+        );
+        Ok(Some(remaining))
+    }
+
+    /// Returns the current status of the controlled output.
+    pub async fn output_status(&self) -> Result<OutputStatus> {
+        match &self.output {
+            Output::Switch => Ok(self.client.get_switch_status(self.switch_id).await?.into()),
+            Output::Dimmer { .. } => Ok(self.client.get_light_status(self.switch_id).await?.into()),
+            Output::Colour { curve, .. } if curve.is_temperature() => {
+                Ok(self.client.get_cct_status(self.switch_id).await?.into())
+            }
+            Output::Colour { .. } => Ok(self.client.get_rgbw_status(self.switch_id).await?.into()),
+        }
+    }
+
+    /// Checks that the device speaks the Gen2 RPC API and provides the controlled
+    /// component, the Schedule service and the KVS, so that it is not scheduled with
+    /// calls it cannot execute. Returns the device's identification.
+    pub async fn check_device(&self) -> Result<DeviceInfo> {
+        let info = match self.client.get_device_info().await {
+            Ok(info) => info,
+            Err(ShellyRpcError::ReqwestError(e)) => return Err(e.into()),
+            Err(e) => {
+                return Err(CustomError::UnsupportedDevice(format!(
+                    "the device does not answer Shelly.GetDeviceInfo, it may be a Gen1 device: {e}"
+                ))
+                .into())
+            }
+        };
+        if info.generation < 2 {
+            return Err(CustomError::UnsupportedDevice(format!(
+                "{} is a Gen{} device, the Gen2 RPC API is required",
+                info.model, info.generation
+            ))
+            .into());
+        }
+
+        let methods = self.client.list_methods().await?;
+        for (service, required) in REQUIRED_METHODS {
+            if let Some(missing) = required
+                .iter()
+                .find(|method| !methods.iter().any(|m| m == *method))
+            {
+                return Err(CustomError::UnsupportedDevice(format!(
+                    "{} does not provide the {service} service, {missing} is missing",
+                    info.model
+                ))
+                .into());
+            }
+        }
+
+        if methods.iter().any(|m| m == "Shelly.GetComponents") {
+            let component = self.component();
+            let components = self.client.get_components().await?;
+            if !components.contains(&component) {
+                let profile = info
+                    .profile
+                    .as_ref()
+                    .map(|profile| format!(" in profile '{profile}'"))
+                    .unwrap_or_default();
+                return Err(CustomError::UnsupportedDevice(format!(
+                    "{}{profile} has no {component}, its components are {}",
+                    info.model,
+                    components.join(", ")
+                ))
+                .into());
+            }
+        }
+        Ok(info)
+    }
+
+    /// Returns the controlled component, e.g. `switch:0`.
+    fn component(&self) -> String {
+        match &self.output {
+            Output::Switch => format!("switch:{}", self.switch_id),
+            Output::Dimmer { .. } => format!("light:{}", self.switch_id),
+            Output::Colour { curve, .. } if curve.is_temperature() => {
+                format!("cct:{}", self.switch_id)
+            }
+            Output::Colour { .. } => format!("rgbw:{}", self.switch_id),
+        }
+    }
+
+    /// Checks that the lamp draws power while its switch is on.
+    /// Returns false if the output is off, so there was nothing to verify, a
+    /// [`CustomError::UnsupportedDevice`] if the output is not metered and a
+    /// [`CustomError::LampFault`] if the consumption is below the threshold.
+    pub async fn verify_lamp(&self, threshold: &LampThreshold) -> Result<bool> {
+        let status = self.output_status().await?;
+        if !status.output {
+            return Ok(false);
+        }
+
+        let Some(apower) = status.apower else {
+            return Err(CustomError::UnsupportedDevice(format!(
+                "{} does not report power, the lamp cannot be verified",
+                self.component()
+            ))
+            .into());
+        };
+
+        if !threshold.is_met(apower, status.current) {
+            return Err(CustomError::LampFault(format!(
+                "Lamp on {} is on but draws {apower}W / {}A, it may be burnt out or disconnected",
+                self.component(),
+                status.current.unwrap_or_default()
+            ))
+            .into());
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the target day length in seconds on the local date of `now`.
+    pub(crate) fn day_length_seconds(day_length: &DayLength, now: i64) -> Result<i64> {
+        let LocalResult::Single(dt) = Local.timestamp_opt(now, 0) else {
+            return Err(CustomError::ChronoError("timestamp out of range").into());
+        };
+
+        let seconds = day_length.seconds_on(dt.date_naive())?;
+        if !matches!(day_length, DayLength::Hours(_)) {
+            debug!("Day length on {} is {seconds}s", dt.date_naive());
+        }
+        Ok(seconds)
+    }
+
+    async fn get_time_sunrise_sunset(&self) -> Result<(i64, i64, i64)> {
+        trace!("get_time_sunrise_sunset");
+        let ((latitude, longitude), timestamp) = self.get_location_time().await?;
+        let (sunrise, sunset) = Self::sunrise_sunset_at(latitude, longitude, timestamp)?;
+        Ok((timestamp, sunrise, sunset))
+    }
+
+    async fn get_location_time(&self) -> Result<((f64, f64), i64)> {
+        let location = self.client.get_location().await?;
         let timestamp = self.client.get_time().await?;
+        Ok((location, timestamp))
+    }
 
+    /// Returns sunrise and sunset at the location on the local date of `timestamp`.
+    pub(crate) fn sunrise_sunset_at(
+        latitude: f64,
+        longitude: f64,
+        timestamp: i64,
+    ) -> Result<(i64, i64)> {
         if let LocalResult::Single(dt) = Local.timestamp_opt(timestamp, 0) {
-            let (sunrise, sunset) =
-                sunrise::sunrise_sunset(latitude, longitude, dt.year(), dt.month(), dt.day());
-            Ok((sunrise, sunset))
+            Ok(sunrise::sunrise_sunset(
+                latitude,
+                longitude,
+                dt.year(),
+                dt.month(),
+                dt.day(),
+            ))
         } else {
             Err(CustomError::ChronoError("timestamp out of range").into())
         }
     }
 
-    async fn create_or_update_schedule(&self, light_on: i64, toggle_after: i64) -> Result<u32> {
-        let switch_id = 0;
-        let enable = light_on > 0;
+    /// Schedules the morning and the evening window, the latter also holds a night break.
+    /// A slot without a window disables its jobs, so that a change of placement does not
+    /// leave stale jobs behind.
+    async fn create_or_update_schedule(
+        &self,
+        morning: Option<(i64, i64)>,
+        evening: Option<(i64, i64)>,
+        timespecs: Timespecs,
+    ) -> Result<u32> {
+        let morning_rev = self
+            .create_or_update_slot(Slot::Morning, morning, timespecs)
+            .await?;
+        let evening_rev = self
+            .create_or_update_slot(Slot::Evening, evening, timespecs)
+            .await?;
 
-        match self.client.get_value(SCHEDULE_JOB_ID).await {
-            Ok(job_id_str) => {
-                // Update
-                let job_id = job_id_str.parse().expect("Not a valid u32");
+        match evening_rev.or(morning_rev) {
+            Some(rev) => Ok(rev),
+            None => Ok(self.client.list_schedule().await?.result.rev),
+        }
+    }
 
+    async fn create_or_update_slot(
+        &self,
+        slot: Slot,
+        window: Option<(i64, i64)>,
+        timespecs: Timespecs,
+    ) -> Result<Option<u32>> {
+        let switch_id = self.switch_id;
+        let key = slot.job_key(&self.namespace, switch_id);
+        let off_key = slot.off_job_key(&self.namespace, switch_id);
+        // Only the default instance inherits the job of the unscoped legacy key, so that
+        // a second instance cannot take it over.
+        let legacy_key = match slot {
+            Slot::Morning if switch_id == 0 && self.namespace.instance() == DEFAULT_INSTANCE => {
+                Some(SCHEDULE_JOB_ID)
+            }
+            _ => None,
+        };
+        let enable = window.is_some();
+        let (light_on, light_off) = window.unwrap_or((-1, -1));
+        // Disabled jobs keep a clock time, there is no window to relate to the sun.
+        let timespecs = if enable { timespecs } else { Timespecs::Clock };
+        let timespec = |timestamp| timespecs.timespec(slot, timestamp);
+
+        let (on_calls, off_calls, switch_off) = match &self.output {
+            Output::Switch if !self.switch_off_job => {
+                let calls = vec![Self::call_switch_on(switch_id, light_off - light_on)];
+                let rev = self
+                    .create_or_update_job(&key, legacy_key, timespec(light_on)?, calls, enable)
+                    .await?;
+                // The toggle_after of the on job switches off, disable a switch-off job
+                // left over from a run with switch_off_job.
+                let off_calls = vec![Self::call_switch_set(switch_id, false)];
+                return Ok(self
+                    .create_or_update_job(&off_key, None, timespec(-1)?, off_calls, false)
+                    .await?
+                    .or(rev));
+            }
+            Output::Switch => (
+                vec![Self::call_switch_set(switch_id, true)],
+                vec![Self::call_switch_set(switch_id, false)],
+                light_off,
+            ),
+            Output::Dimmer { brightness, .. } => {
+                let ramp = self.output.ramp(light_off - light_on);
+                (
+                    vec![Self::call_light_set(
+                        switch_id,
+                        true,
+                        Some(*brightness),
+                        ramp,
+                    )],
+                    vec![Self::call_light_set(switch_id, false, None, ramp)],
+                    light_off - ramp,
+                )
+            }
+            Output::Colour { brightness, curve } => (
+                vec![Self::call_colour_set(
+                    switch_id,
+                    true,
+                    Some(*brightness),
+                    curve.colour_at(0.0),
+                )],
+                vec![Self::call_colour_off(switch_id, curve.is_temperature())],
+                light_off,
+            ),
+        };
+
+        let mut rev = self
+            .create_or_update_job(&key, legacy_key, timespec(light_on)?, on_calls, enable)
+            .await?;
+        rev = self
+            .create_or_update_job(&off_key, None, timespec(switch_off)?, off_calls, enable)
+            .await?
+            .or(rev);
+
+        if let Output::Colour { brightness, curve } = &self.output {
+            let steps = match window {
+                Some((light_on, light_off)) => curve.steps(light_on, light_off),
+                None => Vec::new(),
+            };
+            // Steps are stored under consecutive keys, stop at the first one that is
+            // neither needed nor present from a longer curve.
+            for n in 0..MAX_CURVE_STEPS {
+                let step_key = slot.step_job_key(&self.namespace, switch_id, n);
+                let (timestamp, colour, enable) = match steps.get(n) {
+                    Some((timestamp, colour)) => (*timestamp, *colour, true),
+                    None => (-1, curve.colour_at(0.0), false),
+                };
+                let calls = vec![Self::call_colour_set(
+                    switch_id,
+                    true,
+                    Some(*brightness),
+                    colour,
+                )];
+                match self
+                    .create_or_update_job(&step_key, None, timespec(timestamp)?, calls, enable)
+                    .await?
+                {
+                    Some(step_rev) => rev = Some(step_rev),
+                    None => break,
+                }
+            }
+        }
+
+        Ok(rev)
+    }
+
+    /// Creates or updates the schedule job whose id is stored under `key`.
+    /// Returns the schedule revision, or `None` if there is no job and none is needed.
+    async fn create_or_update_job(
+        &self,
+        key: &str,
+        legacy_key: Option<&str>,
+        timespec: String,
+        calls: Vec<ScheduleJobMethod>,
+        enable: bool,
+    ) -> Result<Option<u32>> {
+        match self.get_job_id(key, legacy_key).await? {
+            Some(job_id) => {
+                // Update
                 // if enable is false, turn the job off or do nothing.
                 if !enable {
                     let result = self.client.list_schedule().await?;
@@ -63,27 +655,58 @@ impl<'a> Controller<'a> {
 
                         if job.id.unwrap() == job_id {
                             if enable == job.enable {
-                                return Ok(result.result.rev);
+                                return Ok(Some(result.result.rev));
                             }
 
                             let result = self.client.disable_schedule(job_id).await?;
-                            return Ok(result.result.rev);
+                            return Ok(Some(result.result.rev));
                         }
                     }
                 }
 
-                let update = Self::new_schedule_job_for_update(
-                    light_on,
-                    switch_id,
-                    toggle_after,
-                    job_id,
-                    enable,
-                )?;
+                let update = Self::new_schedule_job_for_update(timespec, calls, job_id, enable);
                 let result = self.client.update_schedule(&update).await?;
-                Ok(result.result.rev)
+                Ok(Some(result.result.rev))
             }
-            Err(ShellyRpcError::HttpApiError(e)) => {
+            None if !enable => Ok(None),
+            None => {
                 // Create
+                let create = Self::new_schedule_job_for_create(timespec, calls, enable);
+                let result = self.client.create_schedule(&create).await?;
+                let value = result.result.id.to_string();
+                self.client.set_value(key, value.as_str()).await?;
+
+                Ok(Some(result.result.rev))
+            }
+        }
+    }
+
+    /// Looks up the schedule job id stored under `key`. If it is missing, a job id
+    /// stored under `legacy_key` is moved over to `key`.
+    async fn get_job_id(&self, key: &str, legacy_key: Option<&str>) -> Result<Option<u32>> {
+        if let Some(job_id_str) = self.get_value_if_exists(key).await? {
+            return Ok(Some(job_id_str.parse().expect("Not a valid u32")));
+        }
+
+        let Some(legacy_key) = legacy_key else {
+            return Ok(None);
+        };
+
+        match self.get_value_if_exists(legacy_key).await? {
+            Some(job_id_str) => {
+                info!("Migrating schedule job id {job_id_str} from '{legacy_key}' to '{key}'");
+                self.client.set_value(key, &job_id_str).await?;
+                self.client.delete_value(legacy_key).await?;
+                Ok(Some(job_id_str.parse().expect("Not a valid u32")))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn get_value_if_exists(&self, key: &str) -> Result<Option<String>> {
+        match self.client.get_value(key).await {
+            Ok(value) => Ok(Some(value)),
+            Err(ShellyRpcError::HttpApiError(e)) => {
                 if e.error.code != KEY_NOT_FOUND {
                     warn!(
                         "Unexpected error received, refusing to create Schedule job: {:?}",
@@ -91,56 +714,41 @@ impl<'a> Controller<'a> {
                     );
                     return Err(ShellyRpcError::HttpApiError(e).into());
                 }
-
-                let create =
-                    Self::new_schedule_job_for_create(light_on, switch_id, toggle_after, enable)?;
-                let result = self.client.create_schedule(&create).await?;
-                let value = result.result.id.to_string();
-                self.client
-                    .set_value(SCHEDULE_JOB_ID, value.as_str())
-                    .await?;
-
-                Ok(result.result.rev)
+                Ok(None)
             }
             Err(e) => Err(e.into()),
         }
     }
 
     fn new_schedule_job_for_update(
-        light_on: i64,
-        switch_id: u8,
-        toggle_after: i64,
+        timespec: String,
+        calls: Vec<ScheduleJobMethod>,
         job_id: u32,
         enable: bool,
-    ) -> Result<ScheduleJobWithOptionalId, anyhow::Error> {
-        Self::new_schedule_job(light_on, switch_id, toggle_after, Some(job_id), enable)
+    ) -> ScheduleJobWithOptionalId {
+        Self::new_schedule_job(timespec, calls, Some(job_id), enable)
     }
 
     fn new_schedule_job_for_create(
-        light_on: i64,
-        switch_id: u8,
-        toggle_after: i64,
+        timespec: String,
+        calls: Vec<ScheduleJobMethod>,
         enable: bool,
-    ) -> Result<ScheduleJobWithOptionalId, anyhow::Error> {
-        Self::new_schedule_job(light_on, switch_id, toggle_after, None, enable)
+    ) -> ScheduleJobWithOptionalId {
+        Self::new_schedule_job(timespec, calls, None, enable)
     }
 
     fn new_schedule_job(
-        light_on: i64,
-        switch_id: u8,
-        toggle_after: i64,
+        timespec: String,
+        calls: Vec<ScheduleJobMethod>,
         job_id: Option<u32>,
         enable: bool,
-    ) -> Result<ScheduleJobWithOptionalId, anyhow::Error> {
-        let timespec = Self::get_timespec(light_on)?;
-        let calls = vec![Self::call_switch_on(switch_id, toggle_after)];
-        let update = ScheduleJobWithOptionalId {
+    ) -> ScheduleJobWithOptionalId {
+        ScheduleJobWithOptionalId {
             id: job_id,
             enable,
             timespec,
             calls,
-        };
-        Ok(update)
+        }
     }
 
     fn call_switch_on(id: u8, toggle_after: i64) -> ScheduleJobMethod {
@@ -150,7 +758,78 @@ impl<'a> Controller<'a> {
         }
     }
 
-    fn light_on_toggle_after(sunrise: i64, sunset: i64, day_length: i64) -> Result<(i64, i64)> {
+    fn call_switch_set(id: u8, on: bool) -> ScheduleJobMethod {
+        ScheduleJobMethod {
+            method: "switch.set".into(),
+            params: Some(serde_json::json!({"on": on, "id": id})),
+        }
+    }
+
+    fn call_light_set(
+        id: u8,
+        on: bool,
+        brightness: Option<u8>,
+        transition_duration: i64,
+    ) -> ScheduleJobMethod {
+        let mut params = serde_json::json!({"on": on, "id": id});
+        if let Some(brightness) = brightness {
+            params["brightness"] = brightness.into();
+        }
+        if transition_duration > 0 {
+            params["transition_duration"] = transition_duration.into();
+        }
+        ScheduleJobMethod {
+            method: "light.set".into(),
+            params: Some(params),
+        }
+    }
+
+    fn call_colour_set(
+        id: u8,
+        on: bool,
+        brightness: Option<u8>,
+        colour: Colour,
+    ) -> ScheduleJobMethod {
+        let mut params = serde_json::json!({"on": on, "id": id});
+        if let Some(brightness) = brightness {
+            params["brightness"] = brightness.into();
+        }
+        let method = match colour {
+            Colour::Temperature(ct) => {
+                params["ct"] = ct.into();
+                "cct.set"
+            }
+            Colour::Rgb(rgb) => {
+                params["rgb"] = serde_json::json!(rgb);
+                "rgbw.set"
+            }
+        };
+        ScheduleJobMethod {
+            method: method.into(),
+            params: Some(params),
+        }
+    }
+
+    fn call_colour_off(id: u8, temperature: bool) -> ScheduleJobMethod {
+        ScheduleJobMethod {
+            method: if temperature { "cct.set" } else { "rgbw.set" }.into(),
+            params: Some(serde_json::json!({"on": false, "id": id})),
+        }
+    }
+
+    fn window_at(now: i64, windows: &[(i64, i64)]) -> Option<(i64, i64)> {
+        windows
+            .iter()
+            .find(|(light_on, light_off)| now >= *light_on && now < *light_off)
+            .copied()
+    }
+
+    pub(crate) fn light_on_toggle_after(
+        sunrise: i64,
+        sunset: i64,
+        day_length: i64,
+        min_extension: i64,
+    ) -> Result<(i64, i64)> {
         if sunrise >= sunset {
             return Err(CustomError::ChronoError("It's the end of the world").into());
         }
@@ -161,7 +840,7 @@ impl<'a> Controller<'a> {
 
         let light_on = sunset - day_length;
         let toggle_after = sunrise - light_on;
-        if toggle_after < THIRTY_MINS_AS_SEC {
+        if toggle_after < min_extension {
             return Ok((-1, toggle_after));
         }
 
@@ -180,6 +859,20 @@ impl<'a> Controller<'a> {
             Err(CustomError::ChronoError("timestamp out of range").into())
         }
     }
+
+    /// Returns the timespec of a job `offset` seconds after `event` (`sunrise` or
+    /// `sunset`), e.g. `@sunrise-2h30m`. The device resolves offsets to minutes.
+    fn get_sun_timespec(event: &str, offset: i64) -> String {
+        let minutes = (offset as f64 / 60.0).round() as i64;
+        let sign = if minutes < 0 { "-" } else { "+" };
+        let offset = match (minutes.abs() / 60, minutes.abs() % 60) {
+            (0, 0) => String::new(),
+            (0, m) => format!("{sign}{m}m"),
+            (h, 0) => format!("{sign}{h}h"),
+            (h, m) => format!("{sign}{h}h{m}m"),
+        };
+        format!("@{event}{offset} * * 0,1,2,3,4,5,6")
+    }
 }
 
 #[cfg(test)]
@@ -203,6 +896,43 @@ mod tests {
         assert!(result.expect("Unexpected").starts_with(expected));
     }
 
+    #[rstest]
+    #[case("sunrise", -9000, "@sunrise-2h30m * * 0,1,2,3,4,5,6")]
+    #[case("sunrise", -15655, "@sunrise-4h21m * * 0,1,2,3,4,5,6")]
+    #[case("sunset", 3600, "@sunset+1h * * 0,1,2,3,4,5,6")]
+    #[case("sunset", 1790, "@sunset+30m * * 0,1,2,3,4,5,6")]
+    #[case("sunrise", 20, "@sunrise * * 0,1,2,3,4,5,6")]
+    fn get_sun_timespec_parametrized(
+        #[case] event: &str,
+        #[case] offset: i64,
+        #[case] expected: &str,
+    ) {
+        // act
+        let actual = Controller::get_sun_timespec(event, offset);
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+
+    #[rstest]
+    #[case(1703050000, vec![(1703040804, 1703056459)], Some(6459))]
+    #[case(1703040804, vec![(1703040804, 1703056459)], Some(15655))]
+    #[case(1703040803, vec![(1703040804, 1703056459)], None)]
+    #[case(1703056459, vec![(1703040804, 1703056459)], None)]
+    #[case(1703090000, vec![(1703048632, 1703056459), (1703084004, 1703091832)], Some(1832))]
+    #[case(1703050000, vec![], None)]
+    fn window_at_parametrized(
+        #[case] now: i64,
+        #[case] windows: Vec<(i64, i64)>,
+        #[case] expected: Option<i64>,
+    ) {
+        // act
+        let actual = Controller::window_at(now, &windows).map(|(_, light_off)| light_off - now);
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+
     #[rstest]
     #[case(1701413700, 1701442500, 12*60*60, 1701399300, 14400)]
     #[case(1907894520, 1907955120, 12*60*60, -1, -1)]
@@ -216,7 +946,8 @@ mod tests {
         #[case] expected_toggle_after: i64,
     ) {
         // act
-        let result = Controller::light_on_toggle_after(sunrise, sunset, day_length);
+        let result =
+            Controller::light_on_toggle_after(sunrise, sunset, day_length, DEFAULT_MIN_EXTENSION);
 
         // assert
         let (actual_light_on, actual_toggle_after) = result.expect("Unexpected");
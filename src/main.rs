@@ -1,27 +1,246 @@
-use clap::Parser;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveTime, Utc};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use clap_num::number_range;
-use log::{info, LevelFilter};
-use shelly::api::Gen2DeviceClient;
+use daylight_extender::accounting::Ledger;
+use daylight_extender::colour::ColourCurve;
+use daylight_extender::config::{Config, Settings};
+use daylight_extender::daemon::{self, Accounting, DaemonOptions};
+use daylight_extender::discovery::{self, Ipv4Network};
+use daylight_extender::error::CustomError;
+use daylight_extender::fleet::{self, DeviceResult, Scheduled};
+use daylight_extender::gen1::Gen1Controller;
+use daylight_extender::kvs::{KeyNamespace, DEFAULT_INSTANCE};
+use daylight_extender::lamp::LampThreshold;
+use daylight_extender::output::Output;
+use daylight_extender::placement::Placement;
+use daylight_extender::plan::ExtensionMode;
+use daylight_extender::profile::{self, Profile};
+use daylight_extender::program::{DayLength, Program};
+use daylight_extender::tariff::{self, Tariff};
+use daylight_extender::{Controller, DEFAULT_MIN_EXTENSION};
+use log::{info, warn, LevelFilter};
+use shelly::api::{Gen1DeviceClient, Gen2DeviceClient};
+use shelly::mqtt::MqttRpc;
+use shelly::outbound::OutboundServer;
 use simple_logger::SimpleLogger;
 
+/// Exit status of the daemon when a lamp fails its verification.
+const EXIT_LAMP_FAULT: u8 = 3;
+
+/// Seconds between refreshes of the schedules of the devices connected to the server.
+const SERVE_REFRESH: u64 = 24 * 60 * 60;
+
+/// Number of MQTT sessions opened, keeping the client ids of the process unique.
+static MQTT_SESSIONS: AtomicU32 = AtomicU32::new(0);
+
 fn range_0_24(s: &str) -> Result<u8, String> {
     number_range(s, 0, 24)
 }
 
+fn time_of_day(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| format!("invalid time '{s}', expected HH:MM"))
+}
+
+fn reference_location(s: &str) -> Result<(f64, f64), String> {
+    s.split_once(',')
+        .and_then(|(latitude, longitude)| {
+            let latitude = latitude.trim().parse::<f64>().ok()?;
+            let longitude = longitude.trim().parse::<f64>().ok()?;
+            ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+                .then_some((latitude, longitude))
+        })
+        .ok_or_else(|| format!("invalid location '{s}', expected <latitude>,<longitude>"))
+}
+
+fn key_namespace(s: &str) -> Result<KeyNamespace, String> {
+    KeyNamespace::new(s).map_err(|e| e.to_string())
+}
+
 /// Extend daylight to a give total time in hours
 /// by switching on a light switch controlled by
 /// a smart relay.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// IP address of a Gen 2 Shelly Device.
-    #[arg(long, default_value = "192.168.0.232")]
+    /// Configuration file [default: /etc/daylight-extender/config.toml and
+    /// ~/.config/daylight-extender/config.toml]. Flags and environment variables take
+    /// precedence over the file.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Device from the configuration file whose settings to use.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_DEVICE")]
+    device: Option<String>,
+
+    /// IP address of a Shelly device.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_HOST", default_value = "192.168.0.232")]
     host: String,
 
+    /// Password of a device with authentication enabled.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+
+    /// MQTT broker (host or host:port) to send the calls through instead of HTTP, for
+    /// Gen2 devices only reachable through the broker. --host is then the device's MQTT
+    /// topic prefix, by default its device id, e.g. "shellyplus1pm-a8032abe54dc".
+    #[arg(long, env = "DAYLIGHT_EXTENDER_MQTT_BROKER")]
+    mqtt_broker: Option<String>,
+
+    /// User name at the MQTT broker.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_MQTT_USER", requires = "mqtt_broker")]
+    mqtt_user: Option<String>,
+
+    /// Password at the MQTT broker.
+    #[arg(
+        long,
+        env = "DAYLIGHT_EXTENDER_MQTT_PASSWORD",
+        hide_env_values = true,
+        requires = "mqtt_user"
+    )]
+    mqtt_password: Option<String>,
+
+    /// Generation of the device: 1 for the HTTP API of e.g. Shelly 1/1PM relays, 2 for
+    /// the RPC API [default: detected].
+    #[arg(long, env = "DAYLIGHT_EXTENDER_GENERATION", value_parser = clap::value_parser!(u8).range(1..=2))]
+    generation: Option<u8>,
+
+    /// Id of the switch (output channel) to control.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_SWITCH_ID", default_value_t = 0)]
+    switch_id: u8,
+
+    /// Name of this configuration, scopes the bookkeeping keys stored on the device.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_INSTANCE", default_value = DEFAULT_INSTANCE, value_parser = key_namespace)]
+    instance: KeyNamespace,
+
+    /// Drive a dimmer with Light.Set instead of a switch, given as brightness in percent
+    /// and an optional dawn/dusk ramp in minutes, e.g. "80:30". The switch id selects the light.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_DIMMER")]
+    dimmer: Option<Output>,
+
+    /// Drive a CCT or RGBW light along a colour curve over the lit window, e.g.
+    /// "0=2700,0.5=5000" in kelvin or "0=#ff9329,0.5=#ffffff". The switch id selects the light.
+    #[arg(
+        long,
+        env = "DAYLIGHT_EXTENDER_COLOUR_CURVE",
+        conflicts_with = "dimmer"
+    )]
+    colour_curve: Option<ColourCurve>,
+
+    /// Brightness in percent of the light following the colour curve.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_COLOUR_BRIGHTNESS", default_value_t = 100, requires = "colour_curve", value_parser = clap::value_parser!(u8).range(1..=100))]
+    colour_brightness: u8,
+
+    /// Switch the light off with a separate schedule job at the end of the window.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_SWITCH_OFF_JOB", action = clap::ArgAction::SetTrue)]
+    switch_off_job: bool,
+
+    /// Keep running, refresh the schedule daily and switch the light back on
+    /// if it is off inside the lighting window, e.g. after a power cut.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    daemon: bool,
+
+    /// Minutes between switch state checks in daemon mode.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_CHECK_INTERVAL", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    check_interval: u64,
+
+    /// Minimum power in watts a lamp draws when on; enables lamp verification
+    /// in daemon mode.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_MIN_POWER")]
+    min_power: Option<f64>,
+
+    /// Minimum current in amperes a lamp draws when on.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_MIN_CURRENT", requires = "min_power")]
+    min_current: Option<f64>,
+
+    /// Minutes after the scheduled on-time before the lamp is verified.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_VERIFY_DELAY", default_value_t = 2)]
+    verify_delay: u64,
+
+    /// Ledger file recording lamp on-time and energy in daemon mode.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_LEDGER")]
+    ledger: Option<PathBuf>,
+
+    /// Placement of the supplemental light: morning, evening or split [default: morning,
+    /// or the profile's].
+    #[arg(long, env = "DAYLIGHT_EXTENDER_MODE")]
+    mode: Option<ExtensionMode>,
+
+    /// Place the supplemental light where it is cheapest under this tariff instead
+    /// of using a fixed mode, e.g. "00:00-06:00=0.12,0.30".
+    #[arg(
+        long,
+        env = "DAYLIGHT_EXTENDER_CHEAPEST_TARIFF",
+        conflicts_with = "mode"
+    )]
+    cheapest_tariff: Option<Tariff>,
+
+    /// Never switch the light on before this local time (HH:MM) when placing it by tariff.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_EARLIEST_ON", requires = "cheapest_tariff", value_parser = time_of_day)]
+    earliest_on: Option<NaiveTime>,
+
+    /// Switch the light off by this local time (HH:MM) when placing it by tariff.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_LATEST_OFF", requires = "cheapest_tariff", value_parser = time_of_day)]
+    latest_off: Option<NaiveTime>,
+
+    /// Interrupt the night with a light pulse of this many minutes centred on solar
+    /// midnight instead of extending the day.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_NIGHT_BREAK", conflicts_with_all = ["mode", "cheapest_tariff"], value_parser = clap::value_parser!(u64).range(1..))]
+    night_break: Option<u64>,
+
+    /// Write the job times relative to sunrise and sunset, e.g. "@sunrise-2h30m", so
+    /// that the device follows the sun between runs. The extension stays the same, so
+    /// the day length drifts until the next run.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_SUN_RELATIVE", conflicts_with_all = ["cheapest_tariff", "night_break"], action = clap::ArgAction::SetTrue)]
+    sun_relative: bool,
+
     /// Total day length in hours (0 -- 24).
-    #[arg(long, default_value_t = 12, value_parser=range_0_24)]
+    #[arg(long, env = "DAYLIGHT_EXTENDER_TOTAL_DAY_LENGTH", default_value_t = 12, value_parser=range_0_24)]
     total_day_length: u8,
 
+    /// Lighting profile, e.g. "layers-16h", setting the day length, placement, dimmer
+    /// ramp and minimum extension instead of --total-day-length.
+    #[arg(
+        long,
+        env = "DAYLIGHT_EXTENDER_PROFILE",
+        conflicts_with = "total_day_length"
+    )]
+    profile: Option<String>,
+
+    /// Age-based lighting program (TOML) resolving the day length by flock age instead
+    /// of --total-day-length or the profile's day length.
+    #[arg(
+        long,
+        env = "DAYLIGHT_EXTENDER_PROGRAM",
+        conflicts_with = "total_day_length"
+    )]
+    program: Option<PathBuf>,
+
+    /// Extend the day to the natural day length at this reference location, given as
+    /// "<latitude>,<longitude>", e.g. the native region of a plant.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_REFERENCE_LOCATION", conflicts_with_all = ["total_day_length", "program"], value_parser = reference_location, allow_hyphen_values = true)]
+    reference_location: Option<(f64, f64)>,
+
+    /// Date the flock was placed (YYYY-MM-DD), overrides the placement date of the
+    /// program or of the profile's program.
+    #[arg(long, env = "DAYLIGHT_EXTENDER_PLACEMENT_DATE")]
+    placement_date: Option<NaiveDate>,
+
+    /// Directory of user-defined profiles (<name>.toml)
+    /// [default: ~/.config/daylight-extender/profiles].
+    #[arg(long, env = "DAYLIGHT_EXTENDER_PROFILE_DIR")]
+    profile_dir: Option<PathBuf>,
+
+    /// Schedule the device without checking that it provides the controlled component,
+    /// the Schedule service and the KVS.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    skip_device_check: bool,
+
     /// Make the operation more talkative.
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
@@ -29,9 +248,254 @@ struct Cli {
     /// Silent mode.
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     silent: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Export the lighting plan for a date range as an iCalendar (.ics) file.
+    Ics {
+        /// First day of the plan (YYYY-MM-DD).
+        #[arg(long)]
+        from: NaiveDate,
+
+        /// Last day of the plan (YYYY-MM-DD).
+        #[arg(long)]
+        to: NaiveDate,
+
+        /// Latitude of the site, read from the device if omitted.
+        #[arg(long, requires = "longitude", allow_hyphen_values = true)]
+        latitude: Option<f64>,
+
+        /// Longitude of the site, read from the device if omitted.
+        #[arg(long, requires = "latitude", allow_hyphen_values = true)]
+        longitude: Option<f64>,
+
+        /// Title of the calendar events, defaults to the host and switch.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Path of the .ics file to write.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Estimate the energy use and cost of the lighting plan for a date range.
+    Estimate {
+        /// First day of the plan (YYYY-MM-DD).
+        #[arg(long)]
+        from: NaiveDate,
+
+        /// Last day of the plan (YYYY-MM-DD).
+        #[arg(long)]
+        to: NaiveDate,
+
+        /// Latitude of the site, read from the device if omitted.
+        #[arg(long, requires = "longitude", allow_hyphen_values = true)]
+        latitude: Option<f64>,
+
+        /// Longitude of the site, read from the device if omitted.
+        #[arg(long, requires = "latitude", allow_hyphen_values = true)]
+        longitude: Option<f64>,
+
+        /// Power of the lamp in watts.
+        #[arg(long)]
+        lamp_watts: f64,
+
+        /// Price per kWh, either flat (e.g. "0.30") or time-of-use bands
+        /// (e.g. "00:00-06:00=0.12,06:00-22:00=0.30,22:00-24:00=0.12").
+        #[arg(long)]
+        tariff: Tariff,
+
+        /// Placement of the supplemental light: morning, evening or split.
+        #[arg(long, default_value = "morning")]
+        mode: ExtensionMode,
+
+        /// Compare all placements and report the cheapest.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        compare: bool,
+    },
+
+    /// List the built-in and user-defined lighting profiles.
+    Profiles,
+
+    /// Find Shelly devices on the local network via mDNS, or by scanning a network.
+    Discover {
+        /// Seconds to wait for devices to announce themselves.
+        #[arg(long, default_value_t = 5)]
+        timeout: u64,
+
+        /// Probe every host of this network instead of using mDNS, e.g. "192.168.0.0/24".
+        #[arg(long)]
+        scan: Option<Ipv4Network>,
+
+        /// HTTP port probed when scanning.
+        #[arg(long, default_value_t = 80, requires = "scan")]
+        port: u16,
+
+        /// Milliseconds to wait for each device or scanned host to answer.
+        #[arg(long, default_value_t = 1500)]
+        probe_timeout: u64,
+
+        /// Print the devices as [devices.<id>] tables for the configuration file.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        inventory: bool,
+    },
+
+    /// Schedule every device of the configuration file, or the given ones, and print
+    /// a summary. Flags given on the command line apply to all devices.
+    Fleet {
+        /// Devices to schedule [default: all configured devices].
+        devices: Vec<String>,
+
+        /// Number of devices scheduled at the same time.
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..))]
+        parallel: u64,
+    },
+
+    /// Accept the outbound websockets of Gen2 devices behind NAT, configured with
+    /// Ws.SetConfig to connect to ws://<this host>:8080/?token=<token>, and schedule each
+    /// device over its connection when it connects and daily after. A device connecting
+    /// with id <id> uses the settings of [devices.<id>] in the configuration file, if any.
+    Serve {
+        /// Address to listen on.
+        #[arg(long, default_value = "0.0.0.0:8080")]
+        listen: String,
+
+        /// Shared token devices must pass in the query of the server URL.
+        #[arg(
+            long,
+            env = "DAYLIGHT_EXTENDER_SERVE_TOKEN",
+            hide_env_values = true,
+            value_parser = clap::builder::NonEmptyStringValueParser::new()
+        )]
+        token: String,
+    },
+
+    /// Install a script on the device that switches the light by itself, recomputing the
+    /// lighting window every day from the device's location, so no host has to run daily.
+    /// Replaces a script deployed before and disables the schedule jobs of this instance.
+    Script {
+        /// Stop and delete the script instead.
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        remove: bool,
+    },
+
+    /// Print daily and monthly lamp on-time and energy totals from the ledger.
+    Report {
+        /// Rated lamp life in hours, used to estimate the hours remaining.
+        #[arg(long)]
+        lamp_life: Option<f64>,
+    },
 }
 
 impl Cli {
+    /// Parses the command line and fills the settings given neither on the command
+    /// line nor in the environment from the configuration file. Returns the
+    /// configuration and the command line, too.
+    fn load() -> anyhow::Result<(Self, Config, ArgMatches)> {
+        let matches = Cli::command().get_matches();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        let config = Config::load(cli.config.as_deref())?;
+        let settings = match &cli.device {
+            Some(device) => config.device(device)?,
+            None => config.defaults.clone(),
+        };
+        cli.apply(settings, &matches);
+        Ok((cli, config, matches))
+    }
+
+    /// Returns the settings of the configured device `name` with the command line and
+    /// environment taking precedence.
+    fn for_device(matches: &ArgMatches, config: &Config, name: &str) -> anyhow::Result<Self> {
+        let mut cli = Cli::from_arg_matches(matches)?;
+        cli.apply(config.device(name)?, matches);
+        cli.device = Some(name.to_string());
+        Ok(cli)
+    }
+
+    /// Returns the settings of the device that connected to the outbound websocket server
+    /// with the given id: the ones of `[devices.<id>]` if configured, else `cli`'s.
+    fn for_connection(
+        cli: &Cli,
+        config: &Config,
+        matches: &ArgMatches,
+        id: &str,
+    ) -> anyhow::Result<Self> {
+        let mut cli = if config.devices.contains_key(id) {
+            Cli::for_device(matches, config, id)?
+        } else {
+            cli.clone()
+        };
+        // The connection is addressed by the device id.
+        cli.host = id.to_string();
+        Ok(cli)
+    }
+
+    /// Replaces the settings not given on the command line or in the environment with
+    /// the given `settings`. Settings excluding one given on the command line are skipped.
+    fn apply(&mut self, settings: Settings, matches: &ArgMatches) {
+        let given = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+        let any_given = |ids: &[&str]| ids.iter().any(|id| given(id));
+        let day_length_given = any_given(&[
+            "total_day_length",
+            "profile",
+            "program",
+            "reference_location",
+        ]);
+        let placement_given = any_given(&["mode", "cheapest_tariff", "night_break"]);
+        let output_given = any_given(&["dimmer", "colour_curve"]);
+
+        macro_rules! apply {
+            ($skip:expr, $($field:ident),+) => {
+                $(
+                    if let Some(value) = settings.$field.filter(|_| !$skip && !given(stringify!($field))) {
+                        self.$field = value.into();
+                    }
+                )+
+            };
+        }
+        apply!(
+            false,
+            host,
+            password,
+            mqtt_broker,
+            mqtt_user,
+            mqtt_password,
+            generation,
+            switch_id,
+            instance,
+            switch_off_job,
+            sun_relative
+        );
+        apply!(
+            false,
+            check_interval,
+            min_power,
+            min_current,
+            verify_delay,
+            ledger
+        );
+        apply!(false, profile_dir, placement_date, earliest_on, latest_off);
+        apply!(output_given, dimmer, colour_curve, colour_brightness);
+        apply!(placement_given, mode, cheapest_tariff, night_break);
+        apply!(
+            day_length_given,
+            total_day_length,
+            profile,
+            program,
+            reference_location
+        );
+    }
+
     fn log_level(&self) -> LevelFilter {
         if self.silent {
             return LevelFilter::Off;
@@ -43,19 +507,546 @@ impl Cli {
             _ => LevelFilter::Trace,
         }
     }
+
+    fn profile_dir(&self) -> Option<PathBuf> {
+        self.profile_dir.clone().or_else(profile::default_dir)
+    }
+
+    fn profile(&self) -> anyhow::Result<Option<Profile>> {
+        match &self.profile {
+            Some(name) => Ok(Some(profile::find(name, self.profile_dir().as_deref())?)),
+            None => Ok(None),
+        }
+    }
+
+    fn day_length(&self, profile: Option<&Profile>) -> anyhow::Result<DayLength> {
+        match (&self.program, self.reference_location) {
+            (Some(path), _) => Ok(DayLength::Program(
+                Program::load(path)?.with_placement_date(self.placement_date),
+            )),
+            (None, Some((latitude, longitude))) => Ok(DayLength::Reference {
+                latitude,
+                longitude,
+            }),
+            (None, None) => match profile.map(|profile| &profile.day_length) {
+                Some(DayLength::Program(program)) => Ok(DayLength::Program(
+                    program.clone().with_placement_date(self.placement_date),
+                )),
+                Some(day_length) => Ok(day_length.clone()),
+                None => Ok(DayLength::Hours(self.total_day_length)),
+            },
+        }
+    }
+
+    /// Returns the controller of the configured channel of `client`.
+    fn controller<'a>(
+        &self,
+        client: &'a Gen2DeviceClient<'a>,
+        profile: Option<&Profile>,
+    ) -> Controller<'a> {
+        let output = match (self.output(), profile) {
+            (
+                Output::Dimmer {
+                    brightness,
+                    ramp: 0,
+                },
+                Some(profile),
+            ) => Output::Dimmer {
+                brightness,
+                ramp: profile.ramp_seconds(),
+            },
+            (output, _) => output,
+        };
+        Controller::new(client)
+            .with_switch_id(self.switch_id)
+            .with_namespace(self.instance.clone())
+            .with_switch_off_job(self.switch_off_job)
+            .with_sun_relative(self.sun_relative)
+            .with_placement(self.placement(profile))
+            .with_output(output)
+            .with_min_extension(profile.map_or(DEFAULT_MIN_EXTENSION, Profile::min_extension))
+    }
+
+    /// Returns the controller of the configured relay of the Gen1 `client`.
+    fn gen1_controller<'a>(
+        &self,
+        client: &'a Gen1DeviceClient<'a>,
+        profile: Option<&Profile>,
+    ) -> anyhow::Result<Gen1Controller<'a>> {
+        if self.output() != Output::Switch {
+            anyhow::bail!(
+                "Gen1 devices are scheduled as relays, dimmers and colour lights are not supported"
+            );
+        }
+        if self.sun_relative {
+            anyhow::bail!("Gen1 schedule rules take clock times, --sun-relative is not supported");
+        }
+        Ok(Gen1Controller::new(client)
+            .with_switch_id(self.switch_id)
+            .with_placement(self.placement(profile))
+            .with_min_extension(profile.map_or(DEFAULT_MIN_EXTENSION, Profile::min_extension)))
+    }
+
+    fn output(&self) -> Output {
+        match (&self.dimmer, &self.colour_curve) {
+            (Some(dimmer), _) => dimmer.clone(),
+            (None, Some(curve)) => Output::Colour {
+                brightness: self.colour_brightness,
+                curve: curve.clone(),
+            },
+            (None, None) => Output::Switch,
+        }
+    }
+
+    /// Returns the controlled component, e.g. `switch:0`.
+    fn component(&self) -> String {
+        match self.output() {
+            Output::Switch => format!("switch:{}", self.switch_id),
+            Output::Dimmer { .. } => format!("light:{}", self.switch_id),
+            Output::Colour { curve, .. } if curve.is_temperature() => {
+                format!("cct:{}", self.switch_id)
+            }
+            Output::Colour { .. } => format!("rgbw:{}", self.switch_id),
+        }
+    }
+
+    fn placement(&self, profile: Option<&Profile>) -> Placement {
+        if let Some(minutes) = self.night_break {
+            return Placement::NightBreak {
+                duration: minutes as i64 * 60,
+            };
+        }
+
+        match &self.cheapest_tariff {
+            Some(tariff) => Placement::Cheapest {
+                tariff: tariff.clone(),
+                earliest_on: self.earliest_on,
+                latest_off: self.latest_off,
+            },
+            None => Placement::Fixed(
+                self.mode
+                    .or(profile.map(|profile| profile.mode))
+                    .unwrap_or(ExtensionMode::Morning),
+            ),
+        }
+    }
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+async fn main() -> ExitCode {
+    let (cli, config, matches) = match Cli::load() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            return ExitCode::FAILURE;
+        }
+    };
     SimpleLogger::new()
         .with_level(cli.log_level())
         .init()
         .unwrap();
 
-    let client = Gen2DeviceClient::new(&cli.host);
-    let core = daylight_extender::Controller::new(&client);
-    let revision = core.execute(cli.total_day_length).await?;
-    info!("SUCCESS: Schedule (Rev: {revision}) to extend day length created or updated!");
+    match run(&cli, &config, &matches).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            match e.downcast_ref::<CustomError>() {
+                Some(CustomError::LampFault(_)) => ExitCode::from(EXIT_LAMP_FAULT),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+/// Returns the given site location, or the device's if none is given.
+async fn location(
+    cli: &Cli,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+) -> anyhow::Result<(f64, f64)> {
+    match (latitude, longitude) {
+        (Some(latitude), Some(longitude)) => Ok((latitude, longitude)),
+        _ if generation(cli).await? == 1 => {
+            let client = Gen1DeviceClient::new(&cli.host).with_password(cli.password.as_deref());
+            Ok(client.get_location().await?)
+        }
+        _ => {
+            let mqtt = mqtt(cli).await?;
+            let client = Gen2DeviceClient::new(&cli.host)
+                .with_password(cli.password.as_deref())
+                .with_mqtt(mqtt.as_ref());
+            Ok(client.get_location().await?)
+        }
+    }
+}
+
+/// Connects to the MQTT broker of `cli`, if one is configured.
+async fn mqtt(cli: &Cli) -> anyhow::Result<Option<MqttRpc>> {
+    let Some(broker) = &cli.mqtt_broker else {
+        return Ok(None);
+    };
+    let src = format!(
+        "daylight-extender-{}-{}",
+        std::process::id(),
+        MQTT_SESSIONS.fetch_add(1, Ordering::Relaxed)
+    );
+    let credentials = cli
+        .mqtt_user
+        .as_deref()
+        .map(|user| (user, cli.mqtt_password.as_deref().unwrap_or_default()));
+    Ok(Some(MqttRpc::connect(broker, &src, credentials).await?))
+}
+
+/// Returns the configured generation of the device of `cli`, or asks the device.
+async fn generation(cli: &Cli) -> anyhow::Result<u8> {
+    match (cli.generation, &cli.mqtt_broker) {
+        (Some(1), Some(_)) => anyhow::bail!("Gen1 devices cannot be scheduled over MQTT"),
+        (Some(generation), _) => Ok(generation),
+        // Only Gen2 devices take RPC calls over MQTT.
+        (None, Some(_)) => Ok(2),
+        (None, None) => {
+            let info = Gen1DeviceClient::new(&cli.host).get_shelly().await?;
+            info!("{} is a Gen{} device", cli.host, info.generation());
+            Ok(info.generation())
+        }
+    }
+}
+
+/// Schedules the configured `devices`, or all devices, with at most `parallel` at a time.
+async fn fleet(
+    cli: &Cli,
+    config: &Config,
+    matches: &ArgMatches,
+    devices: &[String],
+    parallel: u64,
+) -> anyhow::Result<()> {
+    if cli.daemon {
+        anyhow::bail!("the fleet is scheduled once, --daemon is not supported");
+    }
+    let names: Vec<String> = if devices.is_empty() {
+        config.devices.keys().cloned().collect()
+    } else {
+        devices.to_vec()
+    };
+    if names.is_empty() {
+        anyhow::bail!("no devices configured, add [devices.<name>] tables to the configuration");
+    }
+    let clis = names
+        .iter()
+        .map(|name| Cli::for_device(matches, config, name))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let results = fleet::run_all(clis, parallel as usize, |cli| async move {
+        let outcome = schedule(&cli, None).await.map_err(|e| e.to_string());
+        if let Err(e) = &outcome {
+            warn!("{}: {e}", cli.host);
+        }
+        DeviceResult {
+            device: cli.device.clone().unwrap_or_default(),
+            host: cli.host.clone(),
+            outcome,
+        }
+    })
+    .await;
+
+    print!("{}", fleet::summary(&results));
+    let failed = results
+        .iter()
+        .filter(|result| result.outcome.is_err())
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} devices failed", results.len());
+    }
+    Ok(())
+}
+
+/// Schedules the devices connecting with `token` to the outbound websocket server on
+/// `listen` when they connect, and all connected devices every [`SERVE_REFRESH`] seconds.
+async fn serve(
+    cli: &Cli,
+    config: &Config,
+    matches: &ArgMatches,
+    listen: &str,
+    token: &str,
+) -> anyhow::Result<()> {
+    let src = format!("daylight-extender-{}", std::process::id());
+    let server = OutboundServer::bind(listen, &src, token).await?;
+    let mut refresh = tokio::time::interval(Duration::from_secs(SERVE_REFRESH));
+    refresh.tick().await;
+    loop {
+        let devices = tokio::select! {
+            device = server.next_connection() => match device {
+                Some(device) => vec![device],
+                None => anyhow::bail!("the server stopped accepting devices"),
+            },
+            _ = refresh.tick() => server.devices(),
+        };
+        for device in devices {
+            let outcome = match Cli::for_connection(cli, config, matches, &device) {
+                Ok(cli) => schedule(&cli, Some(&server)).await,
+                Err(e) => Err(e),
+            };
+            match outcome {
+                Ok((revision, windows)) => info!(
+                    "{device}: {} lighting windows scheduled (revision {})",
+                    windows.len(),
+                    revision.unwrap_or_default()
+                ),
+                Err(e) => warn!("{device}: {e}"),
+            }
+        }
+    }
+}
+
+/// Schedules today's lighting windows of the device of `cli`, over the outbound
+/// websocket of the device if a `server` is given.
+async fn schedule(cli: &Cli, server: Option<&OutboundServer>) -> anyhow::Result<Scheduled> {
+    let profile = cli.profile()?;
+    let day_length = cli.day_length(profile.as_ref())?;
+    // Only Gen2 devices open outbound websockets.
+    if server.is_none() && generation(cli).await? == 1 {
+        let windows = schedule_gen1(cli, profile.as_ref(), &day_length).await?;
+        return Ok((None, windows));
+    }
+
+    let mqtt = match server {
+        Some(_) => None,
+        None => mqtt(cli).await?,
+    };
+    let client = Gen2DeviceClient::new(&cli.host)
+        .with_password(cli.password.as_deref())
+        .with_mqtt(mqtt.as_ref())
+        .with_outbound(server);
+    let core = cli.controller(&client, profile.as_ref());
+    if !cli.skip_device_check {
+        core.check_device().await?;
+    }
+    let (revision, windows) = core.execute_with_windows(&day_length).await?;
+    Ok((Some(revision), windows))
+}
+
+/// Writes today's lighting windows of the Gen1 device of `cli` as schedule rules.
+async fn schedule_gen1(
+    cli: &Cli,
+    profile: Option<&Profile>,
+    day_length: &DayLength,
+) -> anyhow::Result<Vec<(i64, i64)>> {
+    let client = Gen1DeviceClient::new(&cli.host).with_password(cli.password.as_deref());
+    let core = cli.gen1_controller(&client, profile)?;
+    if !cli.skip_device_check {
+        let info = core.check_device().await?;
+        info!(
+            "{} ({}, firmware {})",
+            info.mac,
+            info.model.unwrap_or_default(),
+            info.fw.unwrap_or_default()
+        );
+    }
+    core.execute(day_length).await
+}
+
+async fn run(cli: &Cli, config: &Config, matches: &ArgMatches) -> anyhow::Result<()> {
+    match &cli.command {
+        None if generation(cli).await? == 1 => {
+            if cli.daemon {
+                anyhow::bail!("--daemon is not supported on Gen1 devices");
+            }
+            let profile = cli.profile()?;
+            let day_length = cli.day_length(profile.as_ref())?;
+            let windows = schedule_gen1(cli, profile.as_ref(), &day_length).await?;
+            info!(
+                "SUCCESS: Schedule rules for {} lighting windows written!",
+                windows.len()
+            );
+        }
+        None => {
+            let profile = cli.profile()?;
+            let day_length = cli.day_length(profile.as_ref())?;
+            let mqtt = mqtt(cli).await?;
+            let client = Gen2DeviceClient::new(&cli.host)
+                .with_password(cli.password.as_deref())
+                .with_mqtt(mqtt.as_ref());
+            let core = cli.controller(&client, profile.as_ref());
+            if !cli.skip_device_check {
+                let info = core.check_device().await?;
+                info!("{} ({}, firmware {})", info.id, info.model, info.ver);
+            }
+            if cli.daemon {
+                let options = DaemonOptions {
+                    check_interval: Duration::from_secs(cli.check_interval * 60),
+                    lamp_threshold: cli.min_power.map(|min_power| LampThreshold {
+                        min_power,
+                        min_current: cli.min_current,
+                    }),
+                    verify_delay: Duration::from_secs(cli.verify_delay * 60),
+                    accounting: cli.ledger.clone().map(|ledger_path| Accounting {
+                        ledger_path,
+                        channel: format!("{}/{}", cli.host, cli.component()),
+                    }),
+                };
+                daemon::run(&core, &day_length, &options).await?;
+            } else {
+                let revision = core.execute(&day_length).await?;
+                info!(
+                    "SUCCESS: Schedule (Rev: {revision}) to extend day length created or updated!"
+                );
+            }
+        }
+        Some(Command::Ics {
+            from,
+            to,
+            latitude,
+            longitude,
+            name,
+            output,
+        }) => {
+            let profile = cli.profile()?;
+            let day_length = cli.day_length(profile.as_ref())?;
+            let min_extension = profile
+                .as_ref()
+                .map_or(DEFAULT_MIN_EXTENSION, Profile::min_extension);
+            let placement = cli.placement(profile.as_ref());
+            let (latitude, longitude) = location(cli, *latitude, *longitude).await?;
+            let windows = daylight_extender::plan::plan(
+                latitude,
+                longitude,
+                *from,
+                *to,
+                &day_length,
+                min_extension,
+                &placement,
+            )?;
+            let name = name
+                .clone()
+                .unwrap_or_else(|| format!("Supplemental light {} {}", cli.host, cli.component()));
+            let calendar = daylight_extender::ics::to_ics(&windows, &name, Utc::now());
+            std::fs::write(output, calendar)?;
+            info!(
+                "SUCCESS: {} lighting windows written to {}",
+                windows.len(),
+                output.display()
+            );
+        }
+        Some(Command::Estimate {
+            from,
+            to,
+            latitude,
+            longitude,
+            lamp_watts,
+            tariff,
+            mode,
+            compare,
+        }) => {
+            let profile = cli.profile()?;
+            let day_length = cli.day_length(profile.as_ref())?;
+            let min_extension = profile
+                .as_ref()
+                .map_or(DEFAULT_MIN_EXTENSION, Profile::min_extension);
+            let (latitude, longitude) = location(cli, *latitude, *longitude).await?;
+            let modes = if *compare {
+                ExtensionMode::ALL.to_vec()
+            } else {
+                vec![*mode]
+            };
+
+            let mut estimates = Vec::new();
+            for mode in modes {
+                let windows = daylight_extender::plan::plan(
+                    latitude,
+                    longitude,
+                    *from,
+                    *to,
+                    &day_length,
+                    min_extension,
+                    &Placement::Fixed(mode),
+                )?;
+                estimates.push(tariff::estimate(&windows, mode, *lamp_watts, tariff));
+            }
+
+            println!("{:<8} {:>10} {:>10} {:>10}", "Mode", "Hours", "kWh", "Cost");
+            for estimate in &estimates {
+                println!(
+                    "{:<8} {:>10.2} {:>10.3} {:>10.2}",
+                    estimate.mode, estimate.hours, estimate.kwh, estimate.cost
+                );
+            }
+            if let Some(cheapest) = estimates
+                .iter()
+                .min_by(|a, b| a.cost.total_cmp(&b.cost))
+                .filter(|_| *compare)
+            {
+                println!("Cheapest: {}", cheapest.mode);
+            }
+        }
+        Some(Command::Profiles) => {
+            println!(
+                "{:<20} {:>10} {:<8} {:>10} {:>14}",
+                "Profile", "Day (h)", "Mode", "Ramp (min)", "Min ext. (min)"
+            );
+            for (name, profile) in profile::load(cli.profile_dir().as_deref())? {
+                println!(
+                    "{:<20} {:>10} {:<8} {:>10} {:>14}",
+                    name,
+                    profile.hours(),
+                    profile.mode,
+                    profile.ramp,
+                    profile.min_duration
+                );
+            }
+        }
+        Some(Command::Discover {
+            timeout,
+            scan,
+            port,
+            probe_timeout,
+            inventory,
+        }) => {
+            let probe_timeout = Duration::from_millis(*probe_timeout);
+            let devices = match scan {
+                Some(network) => discovery::scan(*network, *port, probe_timeout).await,
+                None => discovery::discover(Duration::from_secs(*timeout), probe_timeout).await?,
+            };
+            if *inventory {
+                print!("{}", discovery::inventory(&devices));
+            } else {
+                print!("{}", discovery::table(&devices));
+            }
+        }
+        Some(Command::Fleet { devices, parallel }) => {
+            fleet(cli, config, matches, devices, *parallel).await?;
+        }
+        Some(Command::Serve { listen, token }) => {
+            serve(cli, config, matches, listen, token).await?;
+        }
+        Some(Command::Script { remove }) => {
+            if generation(cli).await? == 1 {
+                anyhow::bail!("Gen1 devices cannot run scripts");
+            }
+            let profile = cli.profile()?;
+            let day_length = cli.day_length(profile.as_ref())?;
+            let mqtt = mqtt(cli).await?;
+            let client = Gen2DeviceClient::new(&cli.host)
+                .with_password(cli.password.as_deref())
+                .with_mqtt(mqtt.as_ref());
+            let core = cli.controller(&client, profile.as_ref());
+            if *remove {
+                if !core.remove_script().await? {
+                    warn!("No script of instance '{}' found", cli.instance.instance());
+                }
+            } else {
+                if !cli.skip_device_check {
+                    core.check_device().await?;
+                }
+                let id = core.deploy_script(&day_length).await?;
+                info!("SUCCESS: Script {id} deployed, the device now schedules itself!");
+            }
+        }
+        Some(Command::Report { lamp_life }) => {
+            let Some(path) = &cli.ledger else {
+                anyhow::bail!("the report requires a ledger, pass it with --ledger");
+            };
+            print!("{}", Ledger::load(path)?.report(*lamp_life));
+        }
+    }
     Ok(())
 }
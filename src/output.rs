@@ -0,0 +1,132 @@
+use std::str::FromStr;
+
+use crate::colour::ColourCurve;
+use shelly::data::{LightGetStatusResponseResult, SwitchGetStatusResponseResult};
+
+/// The output component the supplemental light is connected to.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Output {
+    /// A relay, switched with `Switch.Set`.
+    #[default]
+    Switch,
+    /// A dimmer, e.g. a Shelly Dimmer 2 or Plus Dimmer, switched with `Light.Set`.
+    /// The light fades in to `brightness` percent over `ramp` seconds at the start of
+    /// the window and fades out over `ramp` seconds before its end.
+    Dimmer { brightness: u8, ramp: i64 },
+    /// A CCT or RGBW light, switched with `CCT.Set` or `RGBW.Set` depending on the
+    /// colours of the `curve`, which it follows in steps over the lit window.
+    Colour { brightness: u8, curve: ColourCurve },
+}
+
+impl Output {
+    /// Returns the ramp in seconds for a window of `duration` seconds. The ramp is
+    /// shortened to half of the window, so the light fades out only after it faded in.
+    pub fn ramp(&self, duration: i64) -> i64 {
+        match self {
+            Output::Switch | Output::Colour { .. } => 0,
+            Output::Dimmer { ramp, .. } => (*ramp).clamp(0, duration / 2),
+        }
+    }
+}
+
+/// A dimmer setting given as `<brightness>[:<ramp minutes>]`, e.g. `80:30`.
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (brightness, ramp) = s.split_once(':').unwrap_or((s, "0"));
+        let brightness = brightness
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|brightness| (1..=100).contains(brightness))
+            .ok_or_else(|| format!("invalid brightness '{brightness}', expected 1 -- 100"))?;
+        let ramp = ramp
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .filter(|ramp| *ramp >= 0)
+            .ok_or_else(|| format!("invalid ramp '{ramp}', expected minutes"))?;
+        Ok(Output::Dimmer {
+            brightness,
+            ramp: ramp * 60,
+        })
+    }
+}
+
+/// State of the controlled output, read from a switch or a light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputStatus {
+    pub output: bool,
+    pub apower: Option<f64>,
+    pub current: Option<f64>,
+    /// Value of the `aenergy.total` counter in watt-hours, if the output is metered.
+    pub energy_total: Option<f64>,
+}
+
+impl From<SwitchGetStatusResponseResult> for OutputStatus {
+    fn from(status: SwitchGetStatusResponseResult) -> Self {
+        Self {
+            output: status.output,
+            apower: status.apower,
+            current: status.current,
+            energy_total: status.aenergy.map(|aenergy| aenergy.total),
+        }
+    }
+}
+
+impl From<LightGetStatusResponseResult> for OutputStatus {
+    fn from(status: LightGetStatusResponseResult) -> Self {
+        Self {
+            output: status.output,
+            apower: status.apower,
+            current: status.current,
+            energy_total: status.aenergy.map(|aenergy| aenergy.total),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("80", Output::Dimmer { brightness: 80, ramp: 0 })]
+    #[case("100:30", Output::Dimmer { brightness: 100, ramp: 1800 })]
+    fn from_str_valid(#[case] s: &str, #[case] expected: Output) {
+        // act
+        let actual = s.parse::<Output>();
+
+        // assert
+        assert_eq!(expected, actual.expect("Unexpected"));
+    }
+
+    #[rstest]
+    #[case("0")]
+    #[case("101")]
+    #[case("80:-5")]
+    #[case("bright")]
+    fn from_str_invalid(#[case] s: &str) {
+        // act
+        let result = s.parse::<Output>();
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn ramp_shortened_to_half_window() {
+        // arrange
+        let output = Output::Dimmer {
+            brightness: 80,
+            ramp: 3600,
+        };
+
+        // act
+        let actual = output.ramp(1800);
+
+        // assert
+        assert_eq!(900, actual);
+    }
+}
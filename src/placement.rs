@@ -0,0 +1,242 @@
+use anyhow::Result;
+use chrono::{Local, LocalResult, NaiveTime, TimeZone};
+
+use crate::error::CustomError;
+use crate::plan::{self, ExtensionMode, Slots};
+use crate::tariff::Tariff;
+
+/// Decides where the supplemental light is placed on a given day.
+#[derive(Debug, Clone)]
+pub enum Placement {
+    /// Always use the same extension mode.
+    Fixed(ExtensionMode),
+    /// Use the extension mode with the lowest cost under `tariff` that keeps the light
+    /// off before `earliest_on` and from `latest_off` on (local time).
+    Cheapest {
+        tariff: Tariff,
+        earliest_on: Option<NaiveTime>,
+        latest_off: Option<NaiveTime>,
+    },
+    /// Instead of extending the day, interrupt the night with a light pulse of
+    /// `duration` seconds centred on solar midnight.
+    NightBreak { duration: i64 },
+}
+
+impl Default for Placement {
+    fn default() -> Self {
+        Placement::Fixed(ExtensionMode::Morning)
+    }
+}
+
+impl Placement {
+    /// Returns the supplemental lighting windows before sunrise and after sunset of the day
+    /// with the given `sunrise` and `sunset`. A night break occupies the slot after sunset.
+    pub fn slots(
+        &self,
+        sunrise: i64,
+        sunset: i64,
+        day_length: i64,
+        min_extension: i64,
+    ) -> Result<Slots> {
+        match self {
+            Placement::Fixed(mode) => mode.slots(sunrise, sunset, day_length, min_extension),
+            Placement::Cheapest {
+                tariff,
+                earliest_on,
+                latest_off,
+            } => cheapest(
+                tariff,
+                *earliest_on,
+                *latest_off,
+                sunrise,
+                sunset,
+                day_length,
+                min_extension,
+            )?
+            .slots(sunrise, sunset, day_length, min_extension),
+            Placement::NightBreak { duration } => {
+                Ok((None, Some(plan::night_break(sunrise, sunset, *duration)?)))
+            }
+        }
+    }
+
+    /// Returns the supplemental lighting windows `(start, end)` of the day in order.
+    pub fn windows(
+        &self,
+        sunrise: i64,
+        sunset: i64,
+        day_length: i64,
+        min_extension: i64,
+    ) -> Result<Vec<(i64, i64)>> {
+        let (morning, evening) = self.slots(sunrise, sunset, day_length, min_extension)?;
+        Ok(morning.into_iter().chain(evening).collect())
+    }
+}
+
+/// Returns the extension mode with the lowest cost under `tariff` that keeps the light
+/// within the allowed hours. On ties the modes are preferred in the order morning,
+/// evening, split.
+fn cheapest(
+    tariff: &Tariff,
+    earliest_on: Option<NaiveTime>,
+    latest_off: Option<NaiveTime>,
+    sunrise: i64,
+    sunset: i64,
+    day_length: i64,
+    min_extension: i64,
+) -> Result<ExtensionMode> {
+    let mut cheapest: Option<(ExtensionMode, f64)> = None;
+    for mode in ExtensionMode::ALL {
+        let windows = mode.windows(sunrise, sunset, day_length, min_extension)?;
+        if !windows
+            .iter()
+            .all(|window| is_allowed(*window, earliest_on, latest_off))
+        {
+            continue;
+        }
+
+        let cost: f64 = windows
+            .iter()
+            .map(|(start, end)| tariff.cost(*start, *end, 1.0))
+            .sum();
+        // Ignore rounding differences, so that equal costs keep the preferred mode.
+        if cheapest.is_none_or(|(_, lowest)| cost < lowest - 1e-9) {
+            cheapest = Some((mode, cost));
+        }
+    }
+
+    cheapest.map(|(mode, _)| mode).ok_or_else(|| {
+        CustomError::PlacementError(
+            "no extension mode meets the target day length within the allowed hours".to_string(),
+        )
+        .into()
+    })
+}
+
+/// Returns true if the light is off before `earliest_on` and from `latest_off` on
+/// during the whole `window`.
+fn is_allowed(
+    (start, end): (i64, i64),
+    earliest_on: Option<NaiveTime>,
+    latest_off: Option<NaiveTime>,
+) -> bool {
+    let mut t = start;
+    while t < end {
+        if let LocalResult::Single(dt) = Local.timestamp_opt(t, 0) {
+            let time = dt.time();
+            if earliest_on.is_some_and(|earliest_on| time < earliest_on)
+                || latest_off.is_some_and(|latest_off| time >= latest_off)
+            {
+                return false;
+            }
+        }
+        t = (t.div_euclid(60) + 1) * 60;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_MIN_EXTENSION;
+    use rstest::rstest;
+
+    // Wednesday, 20 December 2023 08:14:19 (sunrise) and 15:53:24 (sunset)
+    const SUNRISE: i64 = 1703056459;
+    const SUNSET: i64 = 1703084004;
+
+    /// Returns the timestamp of the given local time on Wednesday, 20 December 2023,
+    /// as the tariffs and allowed hours are in local time.
+    fn local(hour: u32, min: u32, sec: u32) -> i64 {
+        Local
+            .with_ymd_and_hms(2023, 12, 20, hour, min, sec)
+            .single()
+            .expect("Unexpected")
+            .timestamp()
+    }
+
+    fn time(s: &str) -> Option<NaiveTime> {
+        Some(NaiveTime::parse_from_str(s, "%H:%M").expect("Unexpected"))
+    }
+
+    #[rstest]
+    #[case("0.30", None, None, ExtensionMode::Morning)]
+    #[case("00:00-07:00=0.10,0.30", None, None, ExtensionMode::Morning)]
+    #[case("16:00-22:00=0.10,0.30", None, None, ExtensionMode::Evening)]
+    #[case("00:00-07:00=0.10,0.30", time("05:00"), None, ExtensionMode::Split)]
+    #[case("0.30", time("05:00"), time("19:00"), ExtensionMode::Split)]
+    fn cheapest_parametrized(
+        #[case] tariff: &str,
+        #[case] earliest_on: Option<NaiveTime>,
+        #[case] latest_off: Option<NaiveTime>,
+        #[case] expected: ExtensionMode,
+    ) {
+        // arrange
+        let tariff: Tariff = tariff.parse().expect("Unexpected");
+
+        // act
+        let actual = cheapest(
+            &tariff,
+            earliest_on,
+            latest_off,
+            local(8, 14, 19),
+            local(15, 53, 24),
+            12 * 60 * 60,
+            DEFAULT_MIN_EXTENSION,
+        );
+
+        // assert
+        assert_eq!(expected, actual.expect("Unexpected"));
+    }
+
+    #[test]
+    fn cheapest_infeasible() {
+        // arrange
+        let tariff = Tariff::flat(0.30);
+
+        // act
+        let actual = cheapest(
+            &tariff,
+            time("07:00"),
+            time("17:00"),
+            local(8, 14, 19),
+            local(15, 53, 24),
+            12 * 60 * 60,
+            DEFAULT_MIN_EXTENSION,
+        );
+
+        // assert
+        assert!(actual.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn slots_fixed() {
+        // arrange
+        let placement = Placement::Fixed(ExtensionMode::Evening);
+
+        // act
+        let actual = placement.slots(SUNRISE, SUNSET, 12 * 60 * 60, DEFAULT_MIN_EXTENSION);
+
+        // assert
+        assert_eq!(
+            (None, Some((SUNSET, 1703099659))),
+            actual.expect("Unexpected")
+        );
+    }
+
+    #[test]
+    fn slots_night_break() {
+        // arrange
+        let placement = Placement::NightBreak { duration: 3600 };
+
+        // act
+        let actual = placement.slots(SUNRISE, SUNSET, 12 * 60 * 60, DEFAULT_MIN_EXTENSION);
+
+        // assert
+        // Thursday, 21 December 2023 00:03:51 (solar midnight) +- 30 minutes
+        assert_eq!(
+            (None, Some((1703111631, 1703115231))),
+            actual.expect("Unexpected")
+        );
+    }
+}
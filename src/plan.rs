@@ -0,0 +1,343 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use crate::error::CustomError;
+use crate::placement::Placement;
+use crate::program::DayLength;
+use crate::Controller;
+
+/// The supplemental lighting windows `(start, end)` before sunrise and after sunset.
+pub type Slots = (Option<(i64, i64)>, Option<(i64, i64)>);
+
+/// Where the supplemental light is placed relative to the natural day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtensionMode {
+    /// Light before sunrise, switched off at sunrise.
+    Morning,
+    /// Light from sunset on.
+    Evening,
+    /// Half of the extension before sunrise, half after sunset.
+    Split,
+}
+
+impl ExtensionMode {
+    pub const ALL: [ExtensionMode; 3] = [
+        ExtensionMode::Morning,
+        ExtensionMode::Evening,
+        ExtensionMode::Split,
+    ];
+
+    /// Returns the supplemental lighting windows `(start, end)` before sunrise and after
+    /// sunset of a day with the given `sunrise` and `sunset`, so that the lit day is
+    /// `day_length` seconds long. Extensions shorter than `min_extension` seconds are not
+    /// worth switching and yield no window.
+    pub fn slots(
+        self,
+        sunrise: i64,
+        sunset: i64,
+        day_length: i64,
+        min_extension: i64,
+    ) -> Result<Slots> {
+        let (light_on, toggle_after) =
+            Controller::light_on_toggle_after(sunrise, sunset, day_length, min_extension)?;
+        if light_on < 0 {
+            return Ok((None, None));
+        }
+
+        let extension = toggle_after;
+        let slots = match self {
+            ExtensionMode::Morning => (Some((light_on, sunrise)), None),
+            ExtensionMode::Evening => (None, Some((sunset, sunset + extension))),
+            ExtensionMode::Split => {
+                let morning = extension / 2;
+                let evening = extension - morning;
+                (
+                    Some((sunrise - morning, sunrise)),
+                    Some((sunset, sunset + evening)),
+                )
+            }
+        };
+        Ok(slots)
+    }
+
+    /// Returns the supplemental lighting windows `(start, end)` of the day in order.
+    pub fn windows(
+        self,
+        sunrise: i64,
+        sunset: i64,
+        day_length: i64,
+        min_extension: i64,
+    ) -> Result<Vec<(i64, i64)>> {
+        let (morning, evening) = self.slots(sunrise, sunset, day_length, min_extension)?;
+        Ok(morning.into_iter().chain(evening).collect())
+    }
+}
+
+impl fmt::Display for ExtensionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtensionMode::Morning => f.pad("morning"),
+            ExtensionMode::Evening => f.pad("evening"),
+            ExtensionMode::Split => f.pad("split"),
+        }
+    }
+}
+
+impl FromStr for ExtensionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "morning" => Ok(ExtensionMode::Morning),
+            "evening" => Ok(ExtensionMode::Evening),
+            "split" => Ok(ExtensionMode::Split),
+            _ => Err(format!(
+                "unknown extension mode '{s}', expected morning, evening or split"
+            )),
+        }
+    }
+}
+
+/// Returns the window `(start, end)` of a night break of `duration` seconds centred on
+/// the solar midnight after `sunset`, halfway between sunset and the next sunrise.
+/// The next sunrise is approximated by adding a day to `sunrise`.
+pub fn night_break(sunrise: i64, sunset: i64, duration: i64) -> Result<(i64, i64)> {
+    if sunrise >= sunset {
+        return Err(CustomError::ChronoError("It's the end of the world").into());
+    }
+
+    let night = sunrise + 24 * 60 * 60 - sunset;
+    if duration <= 0 || duration >= night {
+        return Err(CustomError::PlacementError(format!(
+            "night break of {duration}s must be positive and shorter than the night of {night}s"
+        ))
+        .into());
+    }
+
+    let start = sunset + night / 2 - duration / 2;
+    Ok((start, start + duration))
+}
+
+/// A single supplemental lighting window, given as unix timestamps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightingWindow {
+    pub date: NaiveDate,
+    pub start: i64,
+    pub end: i64,
+}
+
+/// Computes the lighting plan for every date in `from..=to` without talking to a device.
+/// Days on which no supplemental light is needed are left out of the plan.
+pub fn plan(
+    latitude: f64,
+    longitude: f64,
+    from: NaiveDate,
+    to: NaiveDate,
+    day_length: &DayLength,
+    min_extension: i64,
+    placement: &Placement,
+) -> Result<Vec<LightingWindow>> {
+    if from > to {
+        return Err(CustomError::ChronoError("start date is after end date").into());
+    }
+
+    let mut windows = Vec::new();
+    for date in from.iter_days().take_while(|date| *date <= to) {
+        let (sunrise, sunset) =
+            sunrise::sunrise_sunset(latitude, longitude, date.year(), date.month(), date.day());
+        let day_length_seconds = day_length.seconds_on(date)?;
+        for (start, end) in placement.windows(sunrise, sunset, day_length_seconds, min_extension)? {
+            windows.push(LightingWindow { date, start, end });
+        }
+    }
+
+    Ok(windows)
+}
+
+/// Returns the largest deviation in seconds of the lit day from `day_length` on the
+/// `days` days from `from` on, if every day is extended by the same `extension` seconds,
+/// as a schedule relative to sunrise or sunset does.
+pub fn sun_relative_deviation(
+    latitude: f64,
+    longitude: f64,
+    from: NaiveDate,
+    days: u32,
+    day_length: &DayLength,
+    extension: i64,
+) -> Result<i64> {
+    let mut deviation = 0;
+    for date in from.iter_days().take(days as usize) {
+        let (sunrise, sunset) =
+            sunrise::sunrise_sunset(latitude, longitude, date.year(), date.month(), date.day());
+        let lit = (sunset - sunrise + extension).min(24 * 60 * 60);
+        deviation = deviation.max((lit - day_length.seconds_on(date)?).abs());
+    }
+    Ok(deviation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+    use crate::DEFAULT_MIN_EXTENSION;
+    use rstest::rstest;
+
+    #[test]
+    fn plan_berlin_december() {
+        // arrange
+        let from = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+        let to = NaiveDate::from_ymd_opt(2023, 12, 22).expect("Unexpected");
+
+        // act
+        let windows = plan(
+            52.516293,
+            13.377713,
+            from,
+            to,
+            &DayLength::Hours(12),
+            DEFAULT_MIN_EXTENSION,
+            &Placement::default(),
+        )
+        .expect("Unexpected");
+
+        // assert
+        assert_eq!(3, windows.len());
+        // Wednesday, 20 December 2023 08:14:19 (sunrise) and 15:53:24 (sunset)
+        assert_eq!(1703056459, windows[0].end);
+        assert_eq!(1703084004 - 12 * 60 * 60, windows[0].start);
+    }
+
+    #[test]
+    fn plan_follows_program() {
+        // arrange
+        let from = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+        let to = NaiveDate::from_ymd_opt(2023, 12, 21).expect("Unexpected");
+        let program = Program::new(vec![(0, 12), (1, 14)])
+            .expect("Unexpected")
+            .with_placement_date(Some(from));
+
+        // act
+        let windows = plan(
+            52.516293,
+            13.377713,
+            from,
+            to,
+            &DayLength::Program(program),
+            DEFAULT_MIN_EXTENSION,
+            &Placement::default(),
+        )
+        .expect("Unexpected");
+
+        // assert
+        assert_eq!(2, windows.len());
+        assert_eq!(1703084004 - 12 * 60 * 60, windows[0].start);
+        assert!(windows[0].start + 24 * 60 * 60 - windows[1].start > 110 * 60);
+    }
+
+    #[test]
+    fn plan_skips_long_days() {
+        // arrange
+        let from = NaiveDate::from_ymd_opt(2023, 6, 20).expect("Unexpected");
+        let to = NaiveDate::from_ymd_opt(2023, 6, 21).expect("Unexpected");
+
+        // act
+        let windows = plan(
+            52.516293,
+            13.377713,
+            from,
+            to,
+            &DayLength::Hours(12),
+            DEFAULT_MIN_EXTENSION,
+            &Placement::default(),
+        )
+        .expect("Unexpected");
+
+        // assert
+        assert!(windows.is_empty());
+    }
+
+    #[rstest]
+    // Wednesday, 20 December 2023 08:14:19 (sunrise) and 15:53:24 (sunset), 15655s short of 12h
+    #[case(ExtensionMode::Morning, vec![(1703040804, 1703056459)])]
+    #[case(ExtensionMode::Evening, vec![(1703084004, 1703099659)])]
+    #[case(ExtensionMode::Split, vec![(1703048632, 1703056459), (1703084004, 1703091832)])]
+    fn windows_parametrized(#[case] mode: ExtensionMode, #[case] expected: Vec<(i64, i64)>) {
+        // act
+        let actual = mode.windows(1703056459, 1703084004, 12 * 60 * 60, DEFAULT_MIN_EXTENSION);
+
+        // assert
+        assert_eq!(expected, actual.expect("Unexpected"));
+    }
+
+    #[test]
+    fn windows_long_natural_day() {
+        // act
+        let actual = ExtensionMode::Split.windows(
+            1703056459,
+            1703084004,
+            7 * 60 * 60 + 60,
+            DEFAULT_MIN_EXTENSION,
+        );
+
+        // assert
+        assert!(actual.expect("Unexpected").is_empty());
+    }
+
+    #[test]
+    fn sun_relative_deviation_berlin_december() {
+        // arrange
+        let from = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+        // 12h less the natural day of 20 December 2023 (08:14:19 -- 15:53:24)
+        let extension = 12 * 60 * 60 - (1703084004 - 1703056459);
+
+        // act
+        let one_day = sun_relative_deviation(
+            52.516293,
+            13.377713,
+            from,
+            1,
+            &DayLength::Hours(12),
+            extension,
+        );
+        let thirty_days = sun_relative_deviation(
+            52.516293,
+            13.377713,
+            from,
+            30,
+            &DayLength::Hours(12),
+            extension,
+        );
+
+        // assert
+        assert_eq!(0, one_day.expect("Unexpected"));
+        let thirty_days = thirty_days.expect("Unexpected");
+        // The days grow by about half an hour until mid January.
+        assert!((20 * 60..40 * 60).contains(&thirty_days), "{thirty_days}");
+    }
+
+    #[test]
+    fn plan_rejects_reversed_range() {
+        // arrange
+        let from = NaiveDate::from_ymd_opt(2023, 12, 22).expect("Unexpected");
+        let to = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+
+        // act
+        let result = plan(
+            52.516293,
+            13.377713,
+            from,
+            to,
+            &DayLength::Hours(12),
+            DEFAULT_MIN_EXTENSION,
+            &Placement::default(),
+        );
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+}
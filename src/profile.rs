@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::config;
+use crate::error::CustomError;
+use crate::plan::ExtensionMode;
+use crate::program::{DayLength, Program};
+use crate::DEFAULT_MIN_EXTENSION;
+
+/// A named lighting program, so operators pick a husbandry program instead of an hour count.
+///
+/// User-defined profiles are TOML files named `<profile>.toml`, e.g.
+///
+/// ```toml
+/// day_length = 16
+/// ramp = 15
+/// mode = "split"
+/// min_duration = 20
+/// ```
+///
+/// A profile whose day length changes with the age of the flock references a program
+/// file instead of the day length, e.g. `program = "broiler.toml"`, relative to the
+/// profile's directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// Target photoperiod, in hours or following a program.
+    pub day_length: DayLength,
+    /// Minutes over which a dimmer fades in and out.
+    pub ramp: u64,
+    /// Placement of the supplemental light.
+    pub mode: ExtensionMode,
+    /// Shortest extension in minutes worth switching the light for.
+    pub min_duration: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    day_length: Option<u8>,
+    program: Option<PathBuf>,
+    #[serde(default)]
+    ramp: u64,
+    #[serde(default = "default_mode")]
+    mode: ExtensionMode,
+    #[serde(default = "default_min_duration")]
+    min_duration: u64,
+}
+
+fn default_mode() -> ExtensionMode {
+    ExtensionMode::Morning
+}
+
+fn default_min_duration() -> u64 {
+    DEFAULT_MIN_EXTENSION as u64 / 60
+}
+
+impl Profile {
+    /// Returns the ramp in seconds.
+    pub fn ramp_seconds(&self) -> i64 {
+        self.ramp as i64 * 60
+    }
+
+    /// Returns the shortest extension in seconds.
+    pub fn min_extension(&self) -> i64 {
+        self.min_duration as i64 * 60
+    }
+
+    /// Returns the day length as shown in profile listings, e.g. `16` or `23/20/18`.
+    pub fn hours(&self) -> String {
+        match &self.day_length {
+            DayLength::Hours(hours) => hours.to_string(),
+            DayLength::Program(program) => program
+                .steps()
+                .iter()
+                .map(|(_, hours)| hours.to_string())
+                .collect::<Vec<_>>()
+                .join("/"),
+            DayLength::Reference { .. } => "reference".to_string(),
+        }
+    }
+}
+
+/// Returns the profiles shipped with the controller.
+pub fn built_in() -> BTreeMap<String, Profile> {
+    // Broilers brooded under near-continuous light, stepped down over the first two
+    // weeks, from the placement date.
+    let broiler_step_down =
+        Program::new(vec![(0, 23), (7, 20), (14, 18)]).expect("Valid built-in program");
+    [
+        // Laying hens in production.
+        (
+            "layers-16h",
+            DayLength::Hours(16),
+            15,
+            ExtensionMode::Morning,
+            30,
+        ),
+        // Broilers after the brooding period.
+        (
+            "broiler-18h",
+            DayLength::Hours(18),
+            15,
+            ExtensionMode::Split,
+            30,
+        ),
+        (
+            "broiler-step-down",
+            DayLength::Program(broiler_step_down),
+            15,
+            ExtensionMode::Split,
+            30,
+        ),
+        // Long-day plants, flowering is triggered by long days.
+        (
+            "long-day-bloom",
+            DayLength::Hours(16),
+            0,
+            ExtensionMode::Evening,
+            15,
+        ),
+        // Short-day plants kept vegetative.
+        (
+            "short-day-veg",
+            DayLength::Hours(13),
+            0,
+            ExtensionMode::Evening,
+            15,
+        ),
+    ]
+    .into_iter()
+    .map(|(name, day_length, ramp, mode, min_duration)| {
+        (
+            name.to_string(),
+            Profile {
+                day_length,
+                ramp,
+                mode,
+                min_duration,
+            },
+        )
+    })
+    .collect()
+}
+
+/// Returns the default directory of user-defined profiles,
+/// `$XDG_CONFIG_HOME/daylight-extender/profiles` or `~/.config/daylight-extender/profiles`.
+pub fn default_dir() -> Option<PathBuf> {
+    config::user_dir().map(|dir| dir.join("profiles"))
+}
+
+/// Returns the built-in profiles together with the user-defined ones in `dir`,
+/// which take precedence over built-in profiles of the same name.
+pub fn load(dir: Option<&Path>) -> Result<BTreeMap<String, Profile>> {
+    let mut profiles = built_in();
+    let Some(dir) = dir.filter(|dir| dir.is_dir()) else {
+        return Ok(profiles);
+    };
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "toml") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let profile = parse(&std::fs::read_to_string(&path)?, dir)
+            .map_err(|e| CustomError::InvalidProfile(format!("profile {}: {e}", path.display())))?;
+        profiles.insert(name.to_string(), profile);
+    }
+    Ok(profiles)
+}
+
+/// Returns the profile `name` from the built-in and user-defined profiles in `dir`.
+pub fn find(name: &str, dir: Option<&Path>) -> Result<Profile> {
+    let mut profiles = load(dir)?;
+    profiles.remove(name).ok_or_else(|| {
+        CustomError::InvalidProfile(format!(
+            "unknown profile '{name}', available: {}",
+            profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+        ))
+        .into()
+    })
+}
+
+/// Parses a profile file, loading its program relative to `dir`.
+fn parse(content: &str, dir: &Path) -> Result<Profile, String> {
+    let file: ProfileFile = toml::from_str(content).map_err(|e| e.to_string())?;
+    let day_length = match (file.day_length, file.program) {
+        (Some(hours), None) if hours > 24 => {
+            return Err(format!("day_length {hours} is not between 0 and 24"));
+        }
+        (Some(hours), None) => DayLength::Hours(hours),
+        (None, Some(program)) => {
+            DayLength::Program(Program::load(&dir.join(program)).map_err(|e| e.to_string())?)
+        }
+        _ => return Err("a profile needs either a day_length or a program".to_string()),
+    };
+    Ok(Profile {
+        day_length,
+        ramp: file.ramp,
+        mode: file.mode,
+        min_duration: file.min_duration,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn parse_defaults() {
+        // act
+        let actual = parse("day_length = 14", Path::new("."));
+
+        // assert
+        assert_eq!(
+            Profile {
+                day_length: DayLength::Hours(14),
+                ramp: 0,
+                mode: ExtensionMode::Morning,
+                min_duration: 30,
+            },
+            actual.expect("Unexpected")
+        );
+    }
+
+    #[rstest]
+    #[case("day_length = 25")]
+    #[case("day_length = 16\nmode = \"noon\"")]
+    #[case("day_length = 16\nphotoperiod = 16")]
+    #[case("ramp = 15")]
+    #[case("day_length = 16\nprogram = \"broiler.toml\"")]
+    fn parse_invalid(#[case] content: &str) {
+        // act
+        let result = parse(content, Path::new("."));
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn parse_program() {
+        // arrange
+        let dir = std::env::temp_dir().join(format!("dle-profile-program-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Unexpected");
+        std::fs::write(
+            dir.join("broiler.toml"),
+            "[[steps]]\nday = 0\nhours = 23\n\n[[steps]]\nweek = 1\nhours = 18",
+        )
+        .expect("Unexpected");
+
+        // act
+        let actual = parse("program = \"broiler.toml\"\nmode = \"split\"", &dir);
+
+        // assert
+        std::fs::remove_dir_all(&dir).expect("Unexpected");
+        let profile = actual.expect("Unexpected");
+        assert_eq!(
+            DayLength::Program(Program::new(vec![(0, 23), (7, 18)]).expect("Unexpected")),
+            profile.day_length
+        );
+        assert_eq!("23/18", profile.hours());
+    }
+
+    #[test]
+    fn built_in_broiler_step_down() {
+        // act
+        let actual = find("broiler-step-down", None);
+
+        // assert
+        let profile = actual.expect("Unexpected");
+        assert_eq!(ExtensionMode::Split, profile.mode);
+        let DayLength::Program(program) = profile.day_length else {
+            panic!("Expected a program");
+        };
+        assert_eq!(23, program.hours_at(0));
+        assert_eq!(18, program.hours_at(20));
+    }
+
+    #[test]
+    fn find_user_defined_overrides_built_in() {
+        // arrange
+        let dir = std::env::temp_dir().join(format!("dle-profiles-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Unexpected");
+        std::fs::write(
+            dir.join("layers-16h.toml"),
+            "day_length = 15\nmode = \"split\"",
+        )
+        .expect("Unexpected");
+
+        // act
+        let actual = find("layers-16h", Some(&dir));
+
+        // assert
+        std::fs::remove_dir_all(&dir).expect("Unexpected");
+        let profile = actual.expect("Unexpected");
+        assert_eq!(DayLength::Hours(15), profile.day_length);
+        assert_eq!(ExtensionMode::Split, profile.mode);
+    }
+
+    #[test]
+    fn find_unknown() {
+        // act
+        let result = find("layers-99h", None);
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+}
@@ -0,0 +1,260 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use crate::error::CustomError;
+
+/// Day length by flock age, e.g. 23 hours in the first days stepping down to 8 hours
+/// and stepping up again at the point of lay.
+///
+/// Programs are TOML files listing the steps by age in days or weeks, e.g.
+///
+/// ```toml
+/// placement_date = "2024-03-01"
+///
+/// [[steps]]
+/// day = 0
+/// hours = 23
+///
+/// [[steps]]
+/// week = 1
+/// hours = 8
+/// ```
+///
+/// A step applies from its age until the next step. The placement date is optional
+/// in the file and can be given on the command line instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// Steps as `(age in days, day length in hours)` in ascending order of age.
+    steps: Vec<(u32, u8)>,
+    placement_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProgramFile {
+    placement_date: Option<NaiveDate>,
+    steps: Vec<StepEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepEntry {
+    day: Option<u32>,
+    week: Option<u32>,
+    hours: u8,
+}
+
+impl Program {
+    pub fn new(steps: Vec<(u32, u8)>) -> Result<Self, CustomError<'static>> {
+        if steps.is_empty() {
+            return Err(CustomError::InvalidProgram(
+                "program has no steps".to_string(),
+            ));
+        }
+        if let Some((age, hours)) = steps.iter().find(|(_, hours)| *hours > 24) {
+            return Err(CustomError::InvalidProgram(format!(
+                "day length {hours}h at day {age} is not between 0 and 24"
+            )));
+        }
+        if steps.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(CustomError::InvalidProgram(
+                "program steps must be in ascending order of age".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            steps,
+            placement_date: None,
+        })
+    }
+
+    /// Loads a program from a TOML file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&content)
+            .map_err(|e| CustomError::InvalidProgram(format!("program {}: {e}", path.display())))?)
+    }
+
+    fn parse(content: &str) -> Result<Self, String> {
+        let file: ProgramFile = toml::from_str(content).map_err(|e| e.to_string())?;
+        let steps = file
+            .steps
+            .into_iter()
+            .map(|step| match (step.day, step.week) {
+                (Some(day), None) => Ok((day, step.hours)),
+                (None, Some(week)) => Ok((week * 7, step.hours)),
+                _ => Err("every step needs either a day or a week".to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let program = Self::new(steps).map_err(|e| e.to_string())?;
+        Ok(program.with_placement_date(file.placement_date))
+    }
+
+    /// Sets the date the flock was placed, replacing the one from the program file
+    /// if given.
+    pub fn with_placement_date(mut self, placement_date: Option<NaiveDate>) -> Self {
+        if placement_date.is_some() {
+            self.placement_date = placement_date;
+        }
+        self
+    }
+
+    pub fn placement_date(&self) -> Option<NaiveDate> {
+        self.placement_date
+    }
+
+    /// Returns the steps as `(age in days, day length in hours)` in ascending order of age.
+    pub fn steps(&self) -> &[(u32, u8)] {
+        &self.steps
+    }
+
+    /// Returns the day length in hours for a flock `age` days old.
+    pub fn hours_at(&self, age: u32) -> u8 {
+        self.steps
+            .iter()
+            .take_while(|(day, _)| *day <= age)
+            .last()
+            .unwrap_or(&self.steps[0])
+            .1
+    }
+}
+
+/// The day length the controller extends to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DayLength {
+    /// The same number of hours every day.
+    Hours(u8),
+    /// Hours following a program by the age of the flock.
+    Program(Program),
+    /// The natural day length at a reference location, e.g. the native region of a
+    /// plant. Local daylight is extended to match it but never shortened.
+    Reference { latitude: f64, longitude: f64 },
+}
+
+impl From<u8> for DayLength {
+    fn from(hours: u8) -> Self {
+        DayLength::Hours(hours)
+    }
+}
+
+impl DayLength {
+    /// Returns the day length in seconds on `date`. Before the placement date the
+    /// program's first step applies, so the house can be prepared.
+    pub fn seconds_on(&self, date: NaiveDate) -> Result<i64> {
+        let hours = match self {
+            DayLength::Hours(hours) => *hours,
+            DayLength::Program(program) => {
+                let placement_date = program.placement_date().ok_or_else(|| {
+                    CustomError::InvalidProgram("program has no placement date".to_string())
+                })?;
+                let age = (date - placement_date).num_days().max(0) as u32;
+                program.hours_at(age)
+            }
+            DayLength::Reference {
+                latitude,
+                longitude,
+            } => {
+                let (sunrise, sunset) = sunrise::sunrise_sunset(
+                    *latitude,
+                    *longitude,
+                    date.year(),
+                    date.month(),
+                    date.day(),
+                );
+                return Ok((sunset - sunrise).clamp(0, 24 * 60 * 60));
+            }
+        };
+        Ok(i64::from(hours) * 60 * 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const BROILER: &str = r#"
+placement_date = "2024-03-01"
+
+[[steps]]
+day = 0
+hours = 23
+
+[[steps]]
+day = 3
+hours = 20
+
+[[steps]]
+week = 1
+hours = 8
+
+[[steps]]
+week = 18
+hours = 14
+"#;
+
+    #[rstest]
+    #[case("2024-02-28", 23)]
+    #[case("2024-03-01", 23)]
+    #[case("2024-03-04", 20)]
+    #[case("2024-03-08", 8)]
+    #[case("2024-07-05", 14)]
+    fn seconds_on_parametrized(#[case] date: &str, #[case] expected: i64) {
+        // arrange
+        let program = Program::parse(BROILER).expect("Unexpected");
+        let date: NaiveDate = date.parse().expect("Unexpected");
+
+        // act
+        let actual = DayLength::Program(program).seconds_on(date);
+
+        // assert
+        assert_eq!(expected * 60 * 60, actual.expect("Unexpected"));
+    }
+
+    #[rstest]
+    #[case("steps = []")]
+    #[case("[[steps]]\nday = 0\nweek = 1\nhours = 16")]
+    #[case("[[steps]]\nday = 0\nhours = 25")]
+    #[case("[[steps]]\nday = 7\nhours = 16\n[[steps]]\nweek = 1\nhours = 8")]
+    fn parse_invalid(#[case] content: &str) {
+        // act
+        let result = Program::parse(content);
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn seconds_on_without_placement_date() {
+        // arrange
+        let program = Program::new(vec![(0, 23)]).expect("Unexpected");
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).expect("Unexpected");
+
+        // act
+        let result = DayLength::Program(program).seconds_on(date);
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn seconds_on_reference() {
+        // arrange
+        let day_length = DayLength::Reference {
+            latitude: 52.516293,
+            longitude: 13.377713,
+        };
+        let date = NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected");
+
+        // act
+        let actual = day_length.seconds_on(date);
+
+        // assert
+        // Wednesday, 20 December 2023 08:14:19 (sunrise) and 15:53:24 (sunset) in Berlin
+        assert_eq!(1703084004 - 1703056459, actual.expect("Unexpected"));
+    }
+}
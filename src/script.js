@@ -0,0 +1,141 @@
+// Extends the day length with a switch, recomputing the lighting windows every day
+// from the device's location and clock. Deployed by daylight-extender, which prepends
+// the CONFIG object:
+//
+//   switch_id      id of the switch
+//   day_length     { hours: 12 }, { reference: [lat, lon] } or
+//                  { program: { placement_day: <days since 1970-01-01>, steps: [[age, hours]] } }
+//   placement      { mode: "morning" | "evening" | "split" } or { night_break: <seconds> }
+//   min_extension  extensions shorter than this many seconds are not switched
+
+let DAY = 86400;
+let RAD = Math.PI / 180;
+
+let state = { day: null, windows: [], on: null };
+
+// Returns [sunrise, sunset] as unix timestamps of the solar day around `t` at the
+// location, following the sunrise equation.
+function sunTimes(t, lat, lon) {
+  let n = Math.ceil(t / DAY + 2440587.5 - 2451545.0 + 0.0008);
+  let jStar = n - lon / 360;
+  let m = (357.5291 + 0.98560028 * jStar) % 360;
+  let c = 1.9148 * Math.sin(m * RAD) + 0.02 * Math.sin(2 * m * RAD) + 0.0003 * Math.sin(3 * m * RAD);
+  let lambda = (m + c + 180 + 102.9372) % 360;
+  let transit = 2451545.0 + jStar + 0.0053 * Math.sin(m * RAD) - 0.0069 * Math.sin(2 * lambda * RAD);
+  let sinD = Math.sin(lambda * RAD) * Math.sin(23.4397 * RAD);
+  let cosD = Math.sqrt(1 - sinD * sinD);
+  let cosW = (Math.sin(-0.833 * RAD) - Math.sin(lat * RAD) * sinD) / (Math.cos(lat * RAD) * cosD);
+  // Polar night and midnight sun
+  let w = cosW > 1 ? 0 : cosW < -1 ? 180 : Math.acos(cosW) / RAD;
+  let noon = (transit - 2440587.5) * DAY;
+  return [Math.round(noon - (w / 360) * DAY), Math.round(noon + (w / 360) * DAY)];
+}
+
+// Returns the target day length in seconds on the local `day`, at local noon `noon`.
+function dayLength(day, noon) {
+  let config = CONFIG.day_length;
+  if (config.reference) {
+    let times = sunTimes(noon, config.reference[0], config.reference[1]);
+    return Math.min(Math.max(times[1] - times[0], 0), DAY);
+  }
+  if (config.program) {
+    let age = Math.max(day - config.program.placement_day, 0);
+    let steps = config.program.steps;
+    let hours = steps[0][1];
+    for (let i = 0; i < steps.length; i++) {
+      if (steps[i][0] <= age) {
+        hours = steps[i][1];
+      }
+    }
+    return hours * 3600;
+  }
+  return config.hours * 3600;
+}
+
+// Returns the lighting windows [start, end] of a day with the given sunrise and sunset.
+function windows(sunrise, sunset, length) {
+  let placement = CONFIG.placement;
+  if (placement.night_break) {
+    let night = sunrise + DAY - sunset;
+    let start = sunset + Math.floor(night / 2) - Math.floor(placement.night_break / 2);
+    return [[start, start + placement.night_break]];
+  }
+
+  let extension = sunrise - (sunset - length);
+  if (extension < CONFIG.min_extension) {
+    return [];
+  }
+  if (placement.mode === "evening") {
+    return [[sunset, sunset + extension]];
+  }
+  if (placement.mode === "split") {
+    let morning = Math.floor(extension / 2);
+    return [[sunrise - morning, sunrise], [sunset, sunset + extension - morning]];
+  }
+  return [[sunrise - extension, sunrise]];
+}
+
+// Returns the value of a string of decimal digits, e.g. "08".
+function decimal(s) {
+  let value = 0;
+  for (let i = 0; i < s.length; i++) {
+    value = value * 10 + "0123456789".indexOf(s.slice(i, i + 1));
+  }
+  return value;
+}
+
+// Returns the offset of local time to UTC in seconds from the device's clock.
+function localOffset(time, now) {
+  let local = decimal(time.slice(0, 2)) * 60 + decimal(time.slice(3, 5));
+  let offset = local - Math.floor((now % DAY) / 60);
+  if (offset > 720) {
+    offset -= 1440;
+  } else if (offset < -720) {
+    offset += 1440;
+  }
+  return offset * 60;
+}
+
+function plan(day, noon) {
+  let location = Shelly.getComponentConfig("sys").location;
+  let times = sunTimes(noon, location.lat, location.lon);
+  // Keep a window of the previous day running past midnight, e.g. a night break.
+  let kept = [];
+  for (let i = 0; i < state.windows.length; i++) {
+    if (state.windows[i][1] > noon - DAY / 2) {
+      kept.push(state.windows[i]);
+    }
+  }
+  state.windows = kept.concat(windows(times[0], times[1], dayLength(day, noon)));
+  state.day = day;
+  print("daylight-extender: windows", JSON.stringify(state.windows));
+}
+
+function tick() {
+  let sys = Shelly.getComponentStatus("sys");
+  if (sys.unixtime === null || sys.time === null) {
+    // The clock is not synchronised yet.
+    return;
+  }
+  let now = sys.unixtime;
+  let offset = localOffset(sys.time, now);
+  let day = Math.floor((now + offset) / DAY);
+  if (day !== state.day) {
+    plan(day, day * DAY - offset + DAY / 2);
+  }
+
+  let on = false;
+  for (let i = 0; i < state.windows.length; i++) {
+    if (now >= state.windows[i][0] && now < state.windows[i][1]) {
+      on = true;
+    }
+  }
+  if (on !== state.on) {
+    Shelly.call("Switch.Set", { id: CONFIG.switch_id, on: on });
+    state.on = on;
+  }
+}
+
+state.on = Shelly.getComponentStatus("switch:" + JSON.stringify(CONFIG.switch_id)).output;
+Timer.set(60 * 1000, true, tick);
+tick();
@@ -0,0 +1,209 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use log::info;
+use shelly::data::ScriptInfo;
+
+use crate::error::CustomError;
+use crate::output::Output;
+use crate::placement::Placement;
+use crate::plan::ExtensionMode;
+use crate::program::DayLength;
+use crate::{Controller, Timespecs};
+
+/// Code of the on-device script, run by the device's mJS interpreter.
+const SCRIPT: &str = include_str!("script.js");
+/// Bytes of code uploaded per `Script.PutCode` call, the device rejects large calls.
+const CHUNK_SIZE: usize = 1024;
+
+/// Returns the `CONFIG` object parameterising the on-device script.
+/// Placing the light by tariff is not supported, the script has no tariff.
+pub fn config(
+    switch_id: u8,
+    day_length: &DayLength,
+    placement: &Placement,
+    min_extension: i64,
+) -> Result<serde_json::Value> {
+    let day_length = match day_length {
+        DayLength::Hours(hours) => serde_json::json!({ "hours": hours }),
+        DayLength::Program(program) => {
+            let placement_date = program.placement_date().ok_or_else(|| {
+                CustomError::InvalidProgram("program has no placement date".to_string())
+            })?;
+            let placement_day = (placement_date - NaiveDate::default()).num_days();
+            serde_json::json!({
+                "program": { "placement_day": placement_day, "steps": program.steps() }
+            })
+        }
+        DayLength::Reference {
+            latitude,
+            longitude,
+        } => serde_json::json!({ "reference": [latitude, longitude] }),
+    };
+    let placement = match placement {
+        Placement::Fixed(mode) => serde_json::json!({ "mode": mode.to_string() }),
+        Placement::NightBreak { duration } => serde_json::json!({ "night_break": duration }),
+        Placement::Cheapest { .. } => {
+            return Err(CustomError::PlacementError(format!(
+                "the script cannot place the light by tariff, use one of the modes {}",
+                ExtensionMode::ALL.map(|mode| mode.to_string()).join(", ")
+            ))
+            .into())
+        }
+    };
+
+    Ok(serde_json::json!({
+        "switch_id": switch_id,
+        "day_length": day_length,
+        "placement": placement,
+        "min_extension": min_extension,
+    }))
+}
+
+/// Returns the code of the on-device script with the given `CONFIG` object.
+pub fn code(config: &serde_json::Value) -> String {
+    format!("let CONFIG = {config};\n\n{SCRIPT}")
+}
+
+/// Splits `code` into chunks of at most [`CHUNK_SIZE`] bytes.
+fn chunks(code: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = code;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(CHUNK_SIZE);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+impl Controller<'_> {
+    /// Installs a script on the device that switches the light by itself, recomputing
+    /// the lighting windows every day from the device's location and clock, so that no
+    /// host has to refresh the schedule. A script installed before is replaced and the
+    /// schedule jobs of this instance are disabled. Returns the id of the script.
+    pub async fn deploy_script(&self, day_length: &DayLength) -> Result<u32> {
+        if self.output != Output::Switch {
+            return Err(CustomError::InvalidConfig(
+                "the script drives a switch, dimmers and colour lights are not supported"
+                    .to_string(),
+            )
+            .into());
+        }
+        let code = code(&config(
+            self.switch_id,
+            day_length,
+            &self.placement,
+            self.min_extension,
+        )?);
+
+        self.remove_script().await?;
+        self.create_or_update_schedule(None, None, Timespecs::Clock)
+            .await?;
+
+        let name = self.namespace.script_name(self.switch_id);
+        let id = self.client.create_script(&name).await?;
+        for (i, chunk) in chunks(&code).into_iter().enumerate() {
+            self.client.put_script_code(id, chunk, i > 0).await?;
+        }
+        self.client.set_script_config(id, true).await?;
+        self.client.start_script(id).await?;
+        info!("Script '{name}' ({id}) deployed and started");
+        Ok(id)
+    }
+
+    /// Stops and deletes the script of this instance. Returns whether there was one.
+    pub async fn remove_script(&self) -> Result<bool> {
+        let Some(script) = self.find_script().await? else {
+            return Ok(false);
+        };
+
+        if script.running {
+            self.client.stop_script(script.id).await?;
+        }
+        self.client.delete_script(script.id).await?;
+        info!("Script '{}' ({}) removed", script.name, script.id);
+        Ok(true)
+    }
+
+    /// Returns the script of this instance on the device, if one is deployed.
+    pub(crate) async fn find_script(&self) -> Result<Option<ScriptInfo>> {
+        let name = self.namespace.script_name(self.switch_id);
+        let scripts = self.client.list_scripts().await?;
+        Ok(scripts.into_iter().find(|script| script.name == name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::Program;
+    use crate::tariff::Tariff;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        DayLength::Hours(14),
+        Placement::Fixed(ExtensionMode::Split),
+        r#"{"day_length":{"hours":14},"min_extension":1800,"placement":{"mode":"split"},"switch_id":1}"#
+    )]
+    #[case(
+        DayLength::Reference { latitude: 52.5, longitude: 13.25 },
+        Placement::NightBreak { duration: 900 },
+        r#"{"day_length":{"reference":[52.5,13.25]},"min_extension":1800,"placement":{"night_break":900},"switch_id":1}"#
+    )]
+    #[case(
+        DayLength::Program(
+            Program::new(vec![(0, 23), (7, 8)])
+                .expect("Unexpected")
+                .with_placement_date(NaiveDate::from_ymd_opt(2024, 3, 1))
+        ),
+        Placement::default(),
+        r#"{"day_length":{"program":{"placement_day":19783,"steps":[[0,23],[7,8]]}},"min_extension":1800,"placement":{"mode":"morning"},"switch_id":1}"#
+    )]
+    fn config_parametrized(
+        #[case] day_length: DayLength,
+        #[case] placement: Placement,
+        #[case] expected: &str,
+    ) {
+        // act
+        let actual = config(1, &day_length, &placement, 1800);
+
+        // assert
+        assert_eq!(expected, actual.expect("Unexpected").to_string());
+    }
+
+    #[test]
+    fn config_cheapest() {
+        // arrange
+        let placement = Placement::Cheapest {
+            tariff: "0.30".parse::<Tariff>().expect("Unexpected"),
+            earliest_on: None,
+            latest_off: None,
+        };
+
+        // act
+        let result = config(0, &DayLength::Hours(12), &placement, 1800);
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn chunks_reassemble() {
+        // arrange
+        let code = code(&serde_json::json!({ "switch_id": 0 }));
+
+        // act
+        let actual = chunks(&code);
+
+        // assert
+        assert!(actual.len() > 1);
+        assert!(actual.iter().all(|chunk| chunk.len() <= CHUNK_SIZE));
+        assert!(actual[0].starts_with("let CONFIG = {\"switch_id\":0};\n"));
+        assert_eq!(code, actual.concat());
+    }
+}
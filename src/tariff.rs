@@ -0,0 +1,215 @@
+use std::str::FromStr;
+
+use chrono::{Local, LocalResult, NaiveTime, TimeZone, Timelike};
+
+use crate::plan::{ExtensionMode, LightingWindow};
+
+const MINUTES_PER_DAY: usize = 24 * 60;
+
+/// Electricity price per kWh by local time of day, with minute resolution.
+///
+/// Parsed from a comma separated list of `HH:MM-HH:MM=price` bands, e.g.
+/// `00:00-06:00=0.12,06:00-22:00=0.30,22:00-24:00=0.12`. Bands may wrap around
+/// midnight. A bare `price` applies to all times not covered by a band, so a flat
+/// tariff is simply `0.30`. Every minute of the day must have a price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tariff {
+    prices: Vec<f64>,
+}
+
+impl Tariff {
+    pub fn flat(price: f64) -> Self {
+        Self {
+            prices: vec![price; MINUTES_PER_DAY],
+        }
+    }
+
+    /// Returns the price per kWh at the given local time of day.
+    pub fn price_at(&self, time: NaiveTime) -> f64 {
+        self.prices[(time.hour() * 60 + time.minute()) as usize]
+    }
+
+    /// Returns the cost of running a load of `watts` from `start` to `end` (unix timestamps).
+    pub fn cost(&self, start: i64, end: i64, watts: f64) -> f64 {
+        let mut cost = 0.0;
+        let mut t = start;
+        while t < end {
+            let next = ((t.div_euclid(60) + 1) * 60).min(end);
+            let price = match Local.timestamp_opt(t, 0) {
+                LocalResult::Single(dt) => self.price_at(dt.time()),
+                _ => self.prices[0],
+            };
+            cost += kwh(next - t, watts) * price;
+            t = next;
+        }
+        cost
+    }
+}
+
+impl FromStr for Tariff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut default = None;
+        let mut prices: Vec<Option<f64>> = vec![None; MINUTES_PER_DAY];
+        for entry in s.split(',').map(str::trim) {
+            let Some((band, price)) = entry.split_once('=') else {
+                default = Some(parse_price(entry)?);
+                continue;
+            };
+
+            let price = parse_price(price)?;
+            let (start, end) = band
+                .split_once('-')
+                .ok_or_else(|| format!("invalid tariff band '{band}', expected HH:MM-HH:MM"))?;
+            let start = parse_minute(start)?;
+            let end = parse_minute(end)?;
+            let len = match (end + MINUTES_PER_DAY - start) % MINUTES_PER_DAY {
+                0 => MINUTES_PER_DAY,
+                len => len,
+            };
+            for minute in start..start + len {
+                prices[minute % MINUTES_PER_DAY] = Some(price);
+            }
+        }
+
+        let prices = prices
+            .into_iter()
+            .enumerate()
+            .map(|(minute, price)| {
+                price.or(default).ok_or_else(|| {
+                    format!(
+                        "tariff has no price for {:02}:{:02}, add a default price",
+                        minute / 60,
+                        minute % 60
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { prices })
+    }
+}
+
+fn parse_price(s: &str) -> Result<f64, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("invalid tariff price '{s}'"))
+}
+
+fn parse_minute(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    if s == "24:00" {
+        return Ok(MINUTES_PER_DAY);
+    }
+    let time = NaiveTime::parse_from_str(s, "%H:%M")
+        .map_err(|_| format!("invalid tariff time '{s}', expected HH:MM"))?;
+    Ok((time.hour() * 60 + time.minute()) as usize)
+}
+
+fn kwh(seconds: i64, watts: f64) -> f64 {
+    watts * seconds as f64 / 3600.0 / 1000.0
+}
+
+/// Expected energy use and cost of a lighting plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub mode: ExtensionMode,
+    pub hours: f64,
+    pub kwh: f64,
+    pub cost: f64,
+}
+
+/// Estimates the energy use and cost of running a lamp of `lamp_watts` during `windows`.
+pub fn estimate(
+    windows: &[LightingWindow],
+    mode: ExtensionMode,
+    lamp_watts: f64,
+    tariff: &Tariff,
+) -> Estimate {
+    let seconds: i64 = windows.iter().map(|w| w.end - w.start).sum();
+    Estimate {
+        mode,
+        hours: seconds as f64 / 3600.0,
+        kwh: kwh(seconds, lamp_watts),
+        cost: windows
+            .iter()
+            .map(|w| tariff.cost(w.start, w.end, lamp_watts))
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("0.30", "12:00", 0.30)]
+    #[case("00:00-06:00=0.12,06:00-22:00=0.30,22:00-24:00=0.12", "05:59", 0.12)]
+    #[case("00:00-06:00=0.12,06:00-22:00=0.30,22:00-24:00=0.12", "06:00", 0.30)]
+    #[case("00:00-06:00=0.12,06:00-22:00=0.30,22:00-24:00=0.12", "23:30", 0.12)]
+    #[case("22:00-06:00=0.12,0.30", "02:00", 0.12)]
+    #[case("22:00-06:00=0.12,0.30", "21:59", 0.30)]
+    fn price_at_parametrized(#[case] spec: &str, #[case] time: &str, #[case] expected: f64) {
+        // arrange
+        let tariff: Tariff = spec.parse().expect("Unexpected");
+        let time = NaiveTime::parse_from_str(time, "%H:%M").expect("Unexpected");
+
+        // act
+        let actual = tariff.price_at(time);
+
+        // assert
+        assert_eq!(expected, actual);
+    }
+
+    #[rstest]
+    #[case("00:00-06:00=0.12")]
+    #[case("abc")]
+    #[case("06:00=0.12,0.30")]
+    #[case("06:00-25:00=0.12,0.30")]
+    fn from_str_invalid(#[case] spec: &str) {
+        // act
+        let result = spec.parse::<Tariff>();
+
+        // assert
+        assert!(result.is_err(), "Expected Error is Ok");
+    }
+
+    #[test]
+    fn estimate_flat() {
+        // arrange
+        let windows = vec![LightingWindow {
+            date: NaiveDate::from_ymd_opt(2023, 12, 20).expect("Unexpected"),
+            start: 1703048400,
+            end: 1703048400 + 2 * 3600,
+        }];
+
+        // act
+        let actual = estimate(&windows, ExtensionMode::Morning, 100.0, &Tariff::flat(0.5));
+
+        // assert
+        assert_eq!(2.0, actual.hours);
+        assert!((actual.kwh - 0.2).abs() < 1e-9);
+        assert!((actual.cost - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cost_across_bands() {
+        // arrange
+        let tariff: Tariff = "00:00-06:00=0.10,0.50".parse().expect("Unexpected");
+        // Wednesday, 20 December 2023 05:00:00 -- 07:00:00
+        let start = Local
+            .with_ymd_and_hms(2023, 12, 20, 5, 0, 0)
+            .single()
+            .expect("Unexpected")
+            .timestamp();
+        let end = start + 2 * 3600;
+
+        // act
+        let actual = tariff.cost(start, end, 1000.0);
+
+        // assert
+        assert!((actual - 0.6).abs() < 1e-9);
+    }
+}
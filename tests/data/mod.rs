@@ -6,6 +6,11 @@ pub mod mockito {
         pub const GET_CONFIG: &str = r#"{"id":1,"method":"Sys.GetConfig"}"#;
         pub const GET_STATUS: &str = r#"{"id":1,"method":"Sys.GetStatus"}"#;
         pub const LIST_SCHEDULE: &str = r#"{"id":1,"method":"Schedule.List"}"#;
+        pub const LIST_SCRIPTS: &str = r#"{"id":1,"method":"Script.List"}"#;
+        pub const GET_DEVICE_INFO: &str = r#"{"id":1,"method":"Shelly.GetDeviceInfo"}"#;
+        pub const LIST_METHODS: &str = r#"{"id":1,"method":"Shelly.ListMethods"}"#;
+        pub const GET_COMPONENTS: &str =
+            r#"{"id":1,"method":"Shelly.GetComponents","params":{"offset":0}}"#;
 
         pub fn get_value(key: &str) -> String {
             serde_json::json!({
@@ -17,6 +22,17 @@ pub mod mockito {
             })
             .to_string()
         }
+        pub fn delete_value(key: &str) -> String {
+            serde_json::json!({
+                "id":1,
+                "method":"KVS.Delete",
+                "params":{
+                    "key": key
+                }
+            })
+            .to_string()
+        }
+
         pub fn set_value(key: &str, value: &str) -> String {
             serde_json::json!({
                 "id":1,
@@ -30,6 +46,14 @@ pub mod mockito {
         }
 
         pub fn create_schedule(light_on: i64, toggle_after: i64) -> String {
+            create_schedule_for_switch(0, light_on, toggle_after)
+        }
+
+        pub fn create_schedule_for_switch(
+            switch_id: u8,
+            light_on: i64,
+            toggle_after: i64,
+        ) -> String {
             let light_on_dt = Local.timestamp_opt(light_on, 0).unwrap();
             serde_json::json!({
                 "id":1,
@@ -42,7 +66,27 @@ pub mod mockito {
                         "params":{
                             "on": true,
                             "toggle_after": toggle_after,
-                            "id":0
+                            "id": switch_id
+                        }
+                    }]
+                }
+            })
+            .to_string()
+        }
+
+        pub fn create_schedule_sun_relative(timespec: &str, toggle_after: i64) -> String {
+            serde_json::json!({
+                "id":1,
+                "method":"Schedule.Create",
+                "params":{
+                    "enable": true,
+                    "timespec": format!("{timespec} * * 0,1,2,3,4,5,6"),
+                    "calls":[{
+                        "method":"switch.set",
+                        "params":{
+                            "on": true,
+                            "toggle_after": toggle_after,
+                            "id": 0
                         }
                     }]
                 }
@@ -50,6 +94,44 @@ pub mod mockito {
             .to_string()
         }
 
+        pub fn create_schedule_with_calls(timestamp: i64, calls: serde_json::Value) -> String {
+            let dt = Local.timestamp_opt(timestamp, 0).unwrap();
+            serde_json::json!({
+                "id":1,
+                "method":"Schedule.Create",
+                "params":{
+                    "enable": true,
+                    "timespec": format!("{} {} {} * * 0,1,2,3,4,5,6", dt.second(), dt.minute(), dt.hour()),
+                    "calls": calls
+                }
+            })
+            .to_string()
+        }
+
+        pub fn get_switch_status(id: u8) -> String {
+            serde_json::json!({
+                "id":1,
+                "method":"Switch.GetStatus",
+                "params":{
+                    "id": id
+                }
+            })
+            .to_string()
+        }
+
+        pub fn set_switch(id: u8, on: bool, toggle_after: i64) -> String {
+            serde_json::json!({
+                "id":1,
+                "method":"Switch.Set",
+                "params":{
+                    "id": id,
+                    "on": on,
+                    "toggle_after": toggle_after
+                }
+            })
+            .to_string()
+        }
+
         pub fn disable_schedule(id: u32) -> String {
             serde_json::json!({
                 "id":1,
@@ -88,6 +170,125 @@ pub mod mockito {
     pub mod with_body {
         use chrono::{Local, TimeZone, Timelike};
 
+        pub fn get_device_info(generation: u8, profile: Option<&str>) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "name": null,
+                    "id": "shellyplus2pm-a8032ab636ec",
+                    "mac": "A8032AB636EC",
+                    "model": "SNSW-102P16EU",
+                    "gen": generation,
+                    "fw_id": "20231107-164738/1.0.8-g8c7bb8d",
+                    "ver": "1.0.8",
+                    "app": "Plus2PM",
+                    "auth_en": false,
+                    "auth_domain": null,
+                    "profile": profile
+                }
+            })
+            .to_string()
+        }
+
+        pub fn list_methods(methods: &[&str]) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "methods": methods
+                }
+            })
+            .to_string()
+        }
+
+        pub fn get_components(keys: &[&str]) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "components": keys.iter().map(|key| serde_json::json!({ "key": key })).collect::<Vec<_>>(),
+                    "cfg_rev": 10,
+                    "offset": 0,
+                    "total": keys.len()
+                }
+            })
+            .to_string()
+        }
+
+        pub fn list_scripts(scripts: &[(u32, &str, bool)]) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "scripts": scripts
+                        .iter()
+                        .map(|(id, name, running)| serde_json::json!({
+                            "id": id,
+                            "name": name,
+                            "enable": running,
+                            "running": running
+                        }))
+                        .collect::<Vec<_>>()
+                }
+            })
+            .to_string()
+        }
+
+        pub fn create_script(id: u32) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "id": id
+                }
+            })
+            .to_string()
+        }
+
+        pub fn put_script_code(len: u32) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "len": len
+                }
+            })
+            .to_string()
+        }
+
+        pub fn start_stop_script(was_running: bool) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "was_running": was_running
+                }
+            })
+            .to_string()
+        }
+
+        pub fn null_result() -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": null
+            })
+            .to_string()
+        }
+
+        pub fn list_schedule_empty(rev: u32) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "jobs": [],
+                    "rev": rev
+                }
+            })
+            .to_string()
+        }
+
         pub fn get_config(tz: &str, lat: f64, lon: f64) -> String {
             serde_json::json!({
                 "id": 1,
@@ -151,6 +352,66 @@ pub mod mockito {
             .to_string()
         }
 
+        pub fn delete_value() -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "rev": 43,
+                }
+            })
+            .to_string()
+        }
+
+        pub fn get_switch_status(id: u8, output: bool) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "id": id,
+                    "source": "init",
+                    "output": output,
+                }
+            })
+            .to_string()
+        }
+
+        pub fn get_switch_status_metered(
+            id: u8,
+            output: bool,
+            apower: f64,
+            current: f64,
+        ) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "id": id,
+                    "source": "schedule",
+                    "output": output,
+                    "apower": apower,
+                    "current": current,
+                    "aenergy": {
+                        "total": 1234.5,
+                        "by_minute": [0.0, 0.0, 0.0],
+                        "minute_ts": 1703050000
+                    }
+                }
+            })
+            .to_string()
+        }
+
+        pub fn set_switch(was_on: bool) -> String {
+            serde_json::json!({
+                "id": 1,
+                "src": "shelly-test-data",
+                "result": {
+                    "was_on": was_on,
+                }
+            })
+            .to_string()
+        }
+
         pub fn create_schedule(id: u32, rev: u32) -> String {
             serde_json::json!({
                 "id": 1,
@@ -205,4 +466,81 @@ pub mod mockito {
             .to_string()
         }
     }
+
+    /// Responses of the Gen1 HTTP API.
+    pub mod gen1 {
+        use chrono::{Local, TimeZone};
+
+        pub fn get_shelly() -> String {
+            serde_json::json!({
+                "type": "SHSW-1",
+                "mac": "98CDAC1F03A8",
+                "auth": false,
+                "fw": "20230913-112003/v1.14.0-gcb84623",
+                "discoverable": false,
+                "longid": 1,
+                "num_outputs": 1
+            })
+            .to_string()
+        }
+
+        pub fn get_settings(lat: f64, lng: f64) -> String {
+            serde_json::json!({
+                "device": {
+                    "type": "SHSW-1",
+                    "mac": "98CDAC1F03A8",
+                    "hostname": "shelly1-98CDAC1F03A8"
+                },
+                "lat": lat,
+                "lng": lng,
+                "tzautodetect": true,
+                "fw": "20230913-112003/v1.14.0-gcb84623"
+            })
+            .to_string()
+        }
+
+        pub fn get_status(time: &str, unix_timestamp: i64) -> String {
+            serde_json::json!({
+                "time": time,
+                "unixtime": unix_timestamp
+            })
+            .to_string()
+        }
+
+        pub fn get_relay_status(ison: bool) -> String {
+            serde_json::json!({
+                "ison": ison,
+                "has_timer": false,
+                "timer_started": 0,
+                "timer_duration": 0,
+                "timer_remaining": 0,
+                "source": "input"
+            })
+            .to_string()
+        }
+
+        /// Returns the schedule rule switching the relay at the local time of `timestamp`.
+        pub fn schedule_rule(timestamp: i64, action: &str) -> String {
+            let dt = Local.timestamp_opt(timestamp, 0).unwrap();
+            format!("{}-0123456-{action}", dt.format("%H%M"))
+        }
+
+        pub fn relay_settings(schedule: bool, rules: &[&str]) -> String {
+            serde_json::json!({
+                "name": null,
+                "appliance_type": "lights",
+                "ison": false,
+                "has_timer": false,
+                "default_state": "off",
+                "btn_type": "toggle",
+                "btn_reverse": 0,
+                "auto_on": 0.0,
+                "auto_off": 0.0,
+                "power": 0.0,
+                "schedule": schedule,
+                "schedule_rules": rules
+            })
+            .to_string()
+        }
+    }
 }
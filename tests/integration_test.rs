@@ -1,6 +1,16 @@
+use daylight_extender::discovery;
+use daylight_extender::error::CustomError;
+use daylight_extender::gen1::Gen1Controller;
+use daylight_extender::kvs::KeyNamespace;
+use daylight_extender::lamp::LampThreshold;
+use daylight_extender::output::Output;
+use daylight_extender::placement::Placement;
+use daylight_extender::plan::ExtensionMode;
+use daylight_extender::program::DayLength;
 use daylight_extender::{Controller, SCHEDULE_JOB_ID};
-use mockito::Server;
-use shelly::api::Gen2DeviceClient;
+use mdns_sd::{IfKind, ServiceDaemon, ServiceInfo};
+use mockito::{Matcher, Server};
+use shelly::api::{Gen1DeviceClient, Gen2DeviceClient};
 
 mod data;
 
@@ -44,7 +54,16 @@ async fn successful_create() {
         .await;
 
     // get_value
+    let key = KeyNamespace::default().job_key(0);
     let get_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&key))
+        .create_async()
+        .await;
+
+    // get_value (legacy)
+    let get_legacy_value_mock = server
         .mock("POST", "/rpc")
         .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
         .with_body(data::mockito::with_body::get_value_error(SCHEDULE_JOB_ID))
@@ -65,23 +84,53 @@ async fn successful_create() {
     // set_value
     let set_value_mock = server
         .mock("POST", "/rpc")
-        .match_body(data::mockito::match_body::set_value(SCHEDULE_JOB_ID, schedule_id).as_str())
+        .match_body(data::mockito::match_body::set_value(&key, schedule_id).as_str())
         .with_body(data::mockito::with_body::set_value().as_str())
         .create_async()
         .await;
 
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = KeyNamespace::default().off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
     let client = Gen2DeviceClient::new(&host);
     let core = Controller::new(&client);
 
     // act
-    let actual = core.execute(day_length).await;
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
 
     // assert
     get_config_mock.assert_async().await;
     get_status_mock.assert_async().await;
     get_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
     create_schedule_mock.assert_async().await;
     set_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
     assert!(actual.is_ok(), "Expected Ok is Error");
     assert_eq!(schedule_revision, actual.expect("Unexpected"));
 }
@@ -126,9 +175,10 @@ async fn successful_update() {
         .await;
 
     // get_value
+    let key = KeyNamespace::default().job_key(0);
     let get_value_mock = server
         .mock("POST", "/rpc")
-        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
         .with_body(data::mockito::with_body::get_value(schedule_id))
         .create_async()
         .await;
@@ -152,16 +202,42 @@ async fn successful_update() {
     // set_value
     let set_value_mock = server
         .mock("POST", "/rpc")
-        .match_body(data::mockito::match_body::set_value(SCHEDULE_JOB_ID, schedule_id).as_str())
+        .match_body(data::mockito::match_body::set_value(&key, schedule_id).as_str())
         .expect(0)
         .create_async()
         .await;
 
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = KeyNamespace::default().off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
     let client = Gen2DeviceClient::new(&host);
     let core = Controller::new(&client);
 
     // act
-    let actual = core.execute(day_length).await;
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
 
     // assert
     get_config_mock.assert_async().await;
@@ -169,6 +245,9 @@ async fn successful_update() {
     get_value_mock.assert_async().await;
     update_schedule_mock.assert_async().await;
     set_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
     assert!(actual.is_ok(), "Expected Ok is Error");
     assert_eq!(schedule_revision, actual.expect("Unexpected"));
 }
@@ -214,9 +293,10 @@ async fn successful_update_disable() {
         .await;
 
     // get_value
+    let key = KeyNamespace::default().job_key(0);
     let get_value_mock = server
         .mock("POST", "/rpc")
-        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
         .with_body(data::mockito::with_body::get_value(schedule_id))
         .create_async()
         .await;
@@ -251,16 +331,42 @@ async fn successful_update_disable() {
     // don't call set_value
     let set_value_mock = server
         .mock("POST", "/rpc")
-        .match_body(data::mockito::match_body::set_value(SCHEDULE_JOB_ID, schedule_id).as_str())
+        .match_body(data::mockito::match_body::set_value(&key, schedule_id).as_str())
         .expect(0)
         .create_async()
         .await;
 
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = KeyNamespace::default().off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
     let client = Gen2DeviceClient::new(&host);
     let core = Controller::new(&client);
 
     // act
-    let actual = core.execute(day_length).await;
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
 
     // assert
     get_config_mock.assert_async().await;
@@ -269,6 +375,9 @@ async fn successful_update_disable() {
     list_schedule_mock.assert_async().await;
     disable_schedule_mock.assert_async().await;
     set_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
     assert!(actual.is_ok(), "Expected Ok is Error");
     assert_eq!(schedule_revision, actual.expect("Unexpected"));
 }
@@ -314,9 +423,10 @@ async fn successful_update_no_action() {
         .await;
 
     // get_value
+    let key = KeyNamespace::default().job_key(0);
     let get_value_mock = server
         .mock("POST", "/rpc")
-        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
         .with_body(data::mockito::with_body::get_value(schedule_id))
         .create_async()
         .await;
@@ -354,16 +464,42 @@ async fn successful_update_no_action() {
     // don't call set_value
     let set_value_mock = server
         .mock("POST", "/rpc")
-        .match_body(data::mockito::match_body::set_value(SCHEDULE_JOB_ID, schedule_id).as_str())
+        .match_body(data::mockito::match_body::set_value(&key, schedule_id).as_str())
         .expect(0)
         .create_async()
         .await;
 
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = KeyNamespace::default().off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
     let client = Gen2DeviceClient::new(&host);
     let core = Controller::new(&client);
 
     // act
-    let actual = core.execute(day_length).await;
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
 
     // assert
     get_config_mock.assert_async().await;
@@ -372,6 +508,2442 @@ async fn successful_update_no_action() {
     list_schedule_mock.assert_async().await;
     update_schedule_mock.assert_async().await;
     set_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_update_disables_off_job() {
+    // arrange
+    let day_length = 12;
+    let day_length_seconds = i64::from(day_length) * 60 * 60;
+    let schedule_id = "1";
+    let off_schedule_id = "2";
+    let schedule_revision = 36;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    let light_on = sunset - day_length_seconds;
+    let toggle_after = sunrise - light_on;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let key = KeyNamespace::default().job_key(0);
+    let get_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
+        .with_body(data::mockito::with_body::get_value(schedule_id))
+        .create_async()
+        .await;
+
+    // update_schedule
+    let update_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::update_schedule(
+                schedule_id.parse().expect("Not a valid u32"),
+                light_on,
+                toggle_after,
+                true,
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::update_schedule(
+            schedule_revision - 1,
+        ))
+        .create_async()
+        .await;
+
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off job of an earlier run with switch_off_job)
+    let off_key = KeyNamespace::default().off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value(off_schedule_id))
+        .create_async()
+        .await;
+
+    // list_schedule
+    let list_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::LIST_SCHEDULE)
+        .with_body(data::mockito::with_body::list_schedule(
+            off_schedule_id.parse().expect("Not a valid u32"),
+            sunrise,
+            0,
+            true,
+            schedule_revision - 1,
+        ))
+        .create_async()
+        .await;
+
+    // disable_schedule
+    let disable_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::disable_schedule(
+                off_schedule_id.parse().expect("Not a valid u32"),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::update_schedule(schedule_revision))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_value_mock.assert_async().await;
+    update_schedule_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    list_schedule_mock.assert_async().await;
+    disable_schedule_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
     assert!(actual.is_ok(), "Expected Ok is Error");
     assert_eq!(schedule_revision, actual.expect("Unexpected"));
 }
+
+#[tokio::test]
+async fn successful_create_second_switch() {
+    // arrange
+    let day_length = 12;
+    let day_length_seconds = i64::from(day_length) * 60 * 60;
+    let switch_id = 1;
+    let key = KeyNamespace::default().job_key(switch_id);
+    let schedule_id = "2";
+    let schedule_revision = 34;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    let light_on = sunset - day_length_seconds;
+    let toggle_after = sunrise - light_on;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&key))
+        .create_async()
+        .await;
+
+    // get_value (legacy), only switch 0 had a legacy key
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(Matcher::Regex(SCHEDULE_JOB_ID.to_string()))
+        .expect(0)
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_for_switch(
+                switch_id,
+                light_on,
+                toggle_after,
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&key, schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(switch_id);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = KeyNamespace::default().off_job_key(switch_id);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(switch_id);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_switch_id(switch_id);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    create_schedule_mock.assert_async().await;
+    set_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_migrate_legacy_key() {
+    // arrange
+    let day_length = 12;
+    let day_length_seconds = i64::from(day_length) * 60 * 60;
+    let namespace = KeyNamespace::default();
+    let key = namespace.job_key(0);
+    let schedule_id = "7";
+    let schedule_revision = 36;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    let light_on = sunset - day_length_seconds;
+    let toggle_after = sunrise - light_on;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&key))
+        .create_async()
+        .await;
+
+    // get_value (legacy)
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::get_value(schedule_id))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&key, schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    // delete_value (legacy)
+    let delete_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::delete_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::delete_value().as_str())
+        .create_async()
+        .await;
+
+    // update_schedule
+    let update_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::update_schedule(
+                schedule_id.parse().expect("Not a valid u32"),
+                light_on,
+                toggle_after,
+                true,
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::update_schedule(schedule_revision))
+        .create_async()
+        .await;
+
+    // get_value (evening)
+    let evening_key = namespace.evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = namespace.off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = namespace.evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_namespace(namespace);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    set_value_mock.assert_async().await;
+    delete_legacy_value_mock.assert_async().await;
+    update_schedule_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn instance_leaves_legacy_key_alone() {
+    // arrange
+    let day_length = 12;
+    let day_length_seconds = i64::from(day_length) * 60 * 60;
+    let namespace = KeyNamespace::new("evening").expect("Unexpected");
+    let key = namespace.job_key(0);
+    let schedule_id = "8";
+    let schedule_revision = 37;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    let light_on = sunset - day_length_seconds;
+    let toggle_after = sunrise - light_on;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&key))
+        .create_async()
+        .await;
+
+    // get_value (legacy), owned by the default instance
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .expect(0)
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::create_schedule(light_on, toggle_after).as_str())
+        .with_body(data::mockito::with_body::create_schedule(
+            schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&key, schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    // get_value (evening)
+    let evening_key = namespace.evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = namespace.off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = namespace.evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_namespace(namespace);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    create_schedule_mock.assert_async().await;
+    set_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_create_with_switch_off_job() {
+    // arrange
+    let day_length = 12;
+    let day_length_seconds = i64::from(day_length) * 60 * 60;
+    let namespace = KeyNamespace::default();
+    let on_key = namespace.job_key(0);
+    let off_key = namespace.off_job_key(0);
+    let on_schedule_id = "1";
+    let off_schedule_id = "2";
+    let schedule_revision = 38;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    let light_on = sunset - day_length_seconds;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_on_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&on_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&on_key))
+        .create_async()
+        .await;
+
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::get_value_error(SCHEDULE_JOB_ID))
+        .create_async()
+        .await;
+
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_on_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_with_calls(
+                light_on,
+                serde_json::json!([{"method": "switch.set", "params": {"on": true, "id": 0}}]),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            on_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision - 1,
+        ))
+        .create_async()
+        .await;
+
+    let create_off_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_with_calls(
+                sunrise,
+                serde_json::json!([{"method": "switch.set", "params": {"on": false, "id": 0}}]),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            off_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_on_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&on_key, on_schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let set_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&off_key, off_schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_switch_off_job(true);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_on_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    create_on_schedule_mock.assert_async().await;
+    create_off_schedule_mock.assert_async().await;
+    set_on_value_mock.assert_async().await;
+    set_off_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_create_with_dimmer() {
+    // arrange
+    let day_length = 12;
+    let day_length_seconds = i64::from(day_length) * 60 * 60;
+    let namespace = KeyNamespace::default();
+    let on_key = namespace.job_key(0);
+    let off_key = namespace.off_job_key(0);
+    let on_schedule_id = "1";
+    let off_schedule_id = "2";
+    let schedule_revision = 39;
+    let brightness = 80;
+    let ramp = 30 * 60;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    let light_on = sunset - day_length_seconds;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_on_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&on_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&on_key))
+        .create_async()
+        .await;
+
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::get_value_error(SCHEDULE_JOB_ID))
+        .create_async()
+        .await;
+
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_on_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_with_calls(
+                light_on,
+                serde_json::json!([{
+                    "method": "light.set",
+                    "params": {"on": true, "id": 0, "brightness": brightness, "transition_duration": ramp}
+                }]),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            on_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision - 1,
+        ))
+        .create_async()
+        .await;
+
+    let create_off_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_with_calls(
+                sunrise - ramp,
+                serde_json::json!([{
+                    "method": "light.set",
+                    "params": {"on": false, "id": 0, "transition_duration": ramp}
+                }]),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            off_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_on_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&on_key, on_schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let set_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&off_key, off_schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_output(Output::Dimmer { brightness, ramp });
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_on_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    create_on_schedule_mock.assert_async().await;
+    create_off_schedule_mock.assert_async().await;
+    set_on_value_mock.assert_async().await;
+    set_off_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_create_with_colour_curve() {
+    // arrange
+    let day_length = 12;
+    let day_length_seconds = i64::from(day_length) * 60 * 60;
+    let namespace = KeyNamespace::default();
+    let on_key = namespace.job_key(0);
+    let off_key = namespace.off_job_key(0);
+    let on_schedule_id = "1";
+    let off_schedule_id = "2";
+    let step_key = namespace.step_job_key(0, 0);
+    let step_schedule_id = "3";
+    let schedule_revision = 42;
+    let brightness = 100;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    let light_on = sunset - day_length_seconds;
+    // Halfway through the 15655s window
+    let step = light_on + 7828;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_on_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&on_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&on_key))
+        .create_async()
+        .await;
+
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::get_value_error(SCHEDULE_JOB_ID))
+        .create_async()
+        .await;
+
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_on_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_with_calls(
+                light_on,
+                serde_json::json!([{
+                    "method": "cct.set",
+                    "params": {"on": true, "id": 0, "brightness": brightness, "ct": 2700}
+                }]),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            on_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision - 2,
+        ))
+        .create_async()
+        .await;
+
+    let create_off_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_with_calls(
+                sunrise,
+                serde_json::json!([{"method": "cct.set", "params": {"on": false, "id": 0}}]),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            off_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision - 1,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_on_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&on_key, on_schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let set_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&off_key, off_schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    // colour step
+    let get_step_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&step_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&step_key))
+        .create_async()
+        .await;
+
+    let create_step_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_with_calls(
+                step,
+                serde_json::json!([{
+                    "method": "cct.set",
+                    "params": {"on": true, "id": 0, "brightness": brightness, "ct": 5000}
+                }]),
+            )
+            .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            step_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    let set_step_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&step_key, step_schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let unused_step_key = namespace.step_job_key(0, 1);
+    let get_unused_step_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&unused_step_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&unused_step_key))
+        .create_async()
+        .await;
+
+    // get_value (evening)
+    let evening_key = KeyNamespace::default().evening_job_key(0);
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = KeyNamespace::default().evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    let evening_step_key = namespace.evening_step_job_key(0, 0);
+    let get_evening_step_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_step_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_step_key))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_output(Output::Colour {
+        brightness,
+        curve: "0=2700,0.5=5000".parse().expect("Unexpected"),
+    });
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_on_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    create_on_schedule_mock.assert_async().await;
+    create_off_schedule_mock.assert_async().await;
+    set_on_value_mock.assert_async().await;
+    set_off_value_mock.assert_async().await;
+    get_step_value_mock.assert_async().await;
+    create_step_schedule_mock.assert_async().await;
+    set_step_value_mock.assert_async().await;
+    get_unused_step_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    get_evening_step_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_create_split() {
+    // arrange
+    let day_length = 12;
+    let namespace = KeyNamespace::default();
+    let morning_key = namespace.job_key(0);
+    let evening_key = namespace.evening_job_key(0);
+    let morning_schedule_id = "1";
+    let evening_schedule_id = "2";
+    let schedule_revision = 40;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+
+    // 15655s short of 12h, split into 7827s before sunrise and 7828s after sunset
+    let morning = 7827;
+    let evening = 7828;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_morning_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&morning_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&morning_key))
+        .create_async()
+        .await;
+
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::get_value_error(SCHEDULE_JOB_ID))
+        .create_async()
+        .await;
+
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = namespace.off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = namespace.evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_morning_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::create_schedule(sunrise - morning, morning).as_str())
+        .with_body(data::mockito::with_body::create_schedule(
+            morning_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision - 1,
+        ))
+        .create_async()
+        .await;
+
+    let create_evening_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::create_schedule(sunset, evening).as_str())
+        .with_body(data::mockito::with_body::create_schedule(
+            evening_schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_morning_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::set_value(&morning_key, morning_schedule_id).as_str(),
+        )
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let set_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::set_value(&evening_key, evening_schedule_id).as_str(),
+        )
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_placement(Placement::Fixed(ExtensionMode::Split));
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_morning_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    create_morning_schedule_mock.assert_async().await;
+    create_evening_schedule_mock.assert_async().await;
+    set_morning_value_mock.assert_async().await;
+    set_evening_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_create_sun_relative_split() {
+    // arrange
+    let day_length = 12;
+    let namespace = KeyNamespace::default();
+    let morning_key = namespace.job_key(0);
+    let evening_key = namespace.evening_job_key(0);
+    let schedule_revision = 41;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+
+    // 15655s short of 12h, split into 7827s (2h10m) before sunrise and 7828s after sunset
+    let morning = 7827;
+    let evening = 7828;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_morning_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&morning_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&morning_key))
+        .create_async()
+        .await;
+
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::get_value_error(SCHEDULE_JOB_ID))
+        .create_async()
+        .await;
+
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = namespace.off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = namespace.evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_morning_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_sun_relative("@sunrise-2h10m", morning)
+                .as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            1,
+            schedule_revision - 1,
+        ))
+        .create_async()
+        .await;
+
+    let create_evening_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            data::mockito::match_body::create_schedule_sun_relative("@sunset", evening).as_str(),
+        )
+        .with_body(data::mockito::with_body::create_schedule(
+            2,
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_morning_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&morning_key, "1").as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let set_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&evening_key, "2").as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client)
+        .with_placement(Placement::Fixed(ExtensionMode::Split))
+        .with_sun_relative(true);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_morning_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    create_morning_schedule_mock.assert_async().await;
+    create_evening_schedule_mock.assert_async().await;
+    set_morning_value_mock.assert_async().await;
+    set_evening_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn sun_relative_rejects_night_break() {
+    // arrange
+    let client = Gen2DeviceClient::new("localhost:1");
+    let core = Controller::new(&client)
+        .with_placement(Placement::NightBreak { duration: 900 })
+        .with_sun_relative(true);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(12)).await;
+
+    // assert
+    let error = actual.expect_err("Expected Error is Ok");
+    assert!(matches!(
+        error.downcast_ref::<CustomError>(),
+        Some(CustomError::PlacementError(_))
+    ));
+}
+
+#[tokio::test]
+async fn successful_create_night_break() {
+    // arrange
+    let day_length = 12;
+    let namespace = KeyNamespace::default();
+    let morning_key = namespace.job_key(0);
+    let evening_key = namespace.evening_job_key(0);
+    let schedule_id = "3";
+    let schedule_revision = 41;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "16:20";
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Thursday, 21 December 2023 00:03:51 (solar midnight) - 30 minutes
+    let light_on = 1703111631;
+    let duration = 60 * 60;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    // get_value
+    let get_morning_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&morning_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&morning_key))
+        .create_async()
+        .await;
+
+    let get_legacy_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(SCHEDULE_JOB_ID).as_str())
+        .with_body(data::mockito::with_body::get_value_error(SCHEDULE_JOB_ID))
+        .create_async()
+        .await;
+
+    let get_evening_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_key))
+        .create_async()
+        .await;
+
+    // get_value (switch-off jobs)
+    let off_key = namespace.off_job_key(0);
+    let get_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&off_key))
+        .create_async()
+        .await;
+
+    let evening_off_key = namespace.evening_off_job_key(0);
+    let get_evening_off_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_value(&evening_off_key).as_str())
+        .with_body(data::mockito::with_body::get_value_error(&evening_off_key))
+        .create_async()
+        .await;
+
+    // create_schedule
+    let create_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::create_schedule(light_on, duration).as_str())
+        .with_body(data::mockito::with_body::create_schedule(
+            schedule_id.parse().expect("Not a valid u32"),
+            schedule_revision,
+        ))
+        .create_async()
+        .await;
+
+    // set_value
+    let set_value_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_value(&evening_key, schedule_id).as_str())
+        .with_body(data::mockito::with_body::set_value().as_str())
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_placement(Placement::NightBreak { duration });
+
+    // act
+    let actual = core.execute(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_morning_value_mock.assert_async().await;
+    get_legacy_value_mock.assert_async().await;
+    get_evening_value_mock.assert_async().await;
+    get_off_value_mock.assert_async().await;
+    get_evening_off_value_mock.assert_async().await;
+    create_schedule_mock.assert_async().await;
+    set_value_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+    assert_eq!(schedule_revision, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_catch_up() {
+    // arrange
+    let day_length = 12;
+    let switch_id = 0;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "06:26";
+    // Wednesday, 20 December 2023 06:26:40
+    let unix_timestamp = 1703050000;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+
+    let remaining = sunrise - unix_timestamp;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    let get_switch_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_switch_status(switch_id).as_str())
+        .with_body(data::mockito::with_body::get_switch_status(
+            switch_id, false,
+        ))
+        .create_async()
+        .await;
+
+    let set_switch_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_switch(switch_id, true, remaining).as_str())
+        .with_body(data::mockito::with_body::set_switch(false))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let actual = core.catch_up(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_switch_status_mock.assert_async().await;
+    set_switch_mock.assert_async().await;
+    assert_eq!(Some(remaining), actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_catch_up_past_midnight() {
+    // arrange
+    let day_length = 12;
+    let switch_id = 0;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "00:20";
+    // Thursday, 21 December 2023 00:20:00
+    let unix_timestamp = 1703114400;
+    // Thursday, 21 December 2023 00:33:51, the end of the night break of 20 December
+    let break_end = 1703115231;
+
+    let remaining = break_end - unix_timestamp;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    let get_switch_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_switch_status(switch_id).as_str())
+        .with_body(data::mockito::with_body::get_switch_status(
+            switch_id, false,
+        ))
+        .create_async()
+        .await;
+
+    let set_switch_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_switch(switch_id, true, remaining).as_str())
+        .with_body(data::mockito::with_body::set_switch(false))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_placement(Placement::NightBreak { duration: 3600 });
+
+    // act
+    let actual = core.catch_up(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_switch_status_mock.assert_async().await;
+    set_switch_mock.assert_async().await;
+    assert_eq!(Some(remaining), actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_catch_up_no_action() {
+    // arrange
+    let day_length = 12;
+    let switch_id = 0;
+
+    let tz = "Europe/Berlin";
+    let lat = 52.516293;
+    let lon = 13.377713;
+
+    let time = "06:26";
+    // Wednesday, 20 December 2023 06:26:40
+    let unix_timestamp = 1703050000;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+
+    let remaining = sunrise - unix_timestamp;
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .with_body(data::mockito::with_body::get_config(tz, lat, lon))
+        .create_async()
+        .await;
+
+    let get_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_STATUS)
+        .with_body(data::mockito::with_body::get_status(time, unix_timestamp))
+        .create_async()
+        .await;
+
+    let get_switch_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_switch_status(switch_id).as_str())
+        .with_body(data::mockito::with_body::get_switch_status(switch_id, true))
+        .create_async()
+        .await;
+
+    // don't call set_switch
+    let set_switch_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::set_switch(switch_id, true, remaining).as_str())
+        .expect(0)
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let actual = core.catch_up(&DayLength::Hours(day_length)).await;
+
+    // assert
+    get_config_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_switch_status_mock.assert_async().await;
+    set_switch_mock.assert_async().await;
+    assert_eq!(None, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn successful_verify_lamp() {
+    // arrange
+    let switch_id = 2;
+    let threshold = LampThreshold {
+        min_power: 5.0,
+        min_current: Some(0.02),
+    };
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_switch_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_switch_status(switch_id).as_str())
+        .with_body(data::mockito::with_body::get_switch_status_metered(
+            switch_id, true, 8.9, 0.04,
+        ))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_switch_id(switch_id);
+
+    // act
+    let actual = core.verify_lamp(&threshold).await;
+
+    // assert
+    get_switch_status_mock.assert_async().await;
+    assert!(actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn failed_verify_lamp() {
+    // arrange
+    let switch_id = 2;
+    let threshold = LampThreshold {
+        min_power: 5.0,
+        min_current: None,
+    };
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_switch_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_switch_status(switch_id).as_str())
+        .with_body(data::mockito::with_body::get_switch_status_metered(
+            switch_id, true, 0.3, 0.001,
+        ))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_switch_id(switch_id);
+
+    // act
+    let actual = core.verify_lamp(&threshold).await;
+
+    // assert
+    get_switch_status_mock.assert_async().await;
+    let error = actual.expect_err("Expected Error is Ok");
+    assert!(matches!(
+        error.downcast_ref::<CustomError>(),
+        Some(CustomError::LampFault(_))
+    ));
+}
+
+#[tokio::test]
+async fn verify_lamp_not_metered() {
+    // arrange
+    let switch_id = 2;
+    let threshold = LampThreshold {
+        min_power: 5.0,
+        min_current: None,
+    };
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_switch_status_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::get_switch_status(switch_id).as_str())
+        .with_body(data::mockito::with_body::get_switch_status(switch_id, true))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_switch_id(switch_id);
+
+    // act
+    let actual = core.verify_lamp(&threshold).await;
+
+    // assert
+    get_switch_status_mock.assert_async().await;
+    let error = actual.expect_err("Expected Error is Ok");
+    assert!(matches!(
+        error.downcast_ref::<CustomError>(),
+        Some(CustomError::UnsupportedDevice(_))
+    ));
+}
+
+#[tokio::test]
+async fn discover_local_responder() {
+    // arrange
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1pm-a8032abe54dc",
+      "result": {
+        "name": "barn-1",
+        "id": "shellyplus1pm-a8032abe54dc",
+        "mac": "A8032ABE54DC",
+        "model": "SNSW-001P16EU",
+        "gen": 2,
+        "fw_id": "20231107-164738/1.0.8-g8c7bb8d",
+        "ver": "1.0.8",
+        "app": "Plus1PM",
+        "auth_en": false,
+        "auth_domain": null
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let port: u16 = server
+        .host_with_port()
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok())
+        .expect("Unexpected");
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Shelly.GetDeviceInfo"}"#)
+        .with_body(mock_body.to_string())
+        .expect_at_least(1)
+        .create_async()
+        .await;
+
+    let responder = ServiceDaemon::new().expect("Unexpected");
+    responder
+        .enable_interface(IfKind::LoopbackV4)
+        .expect("Unexpected");
+    let service = ServiceInfo::new(
+        "_shelly._tcp.local.",
+        "shellyplus1pm-a8032abe54dc",
+        "shellyplus1pm-a8032abe54dc.local.",
+        "127.0.0.1",
+        port,
+        None,
+    )
+    .expect("Unexpected");
+    responder.register(service).expect("Unexpected");
+
+    let browser = ServiceDaemon::new().expect("Unexpected");
+    browser
+        .enable_interface(IfKind::LoopbackV4)
+        .expect("Unexpected");
+
+    // act
+    let result = discovery::discover_with(
+        &browser,
+        std::time::Duration::from_secs(3),
+        std::time::Duration::from_millis(500),
+    )
+    .await;
+
+    // assert
+    let _ = responder.shutdown();
+    let _ = browser.shutdown();
+    mock.assert_async().await;
+    let devices = result.expect("Unexpected");
+    assert_eq!(1, devices.len());
+    assert_eq!(format!("127.0.0.1:{port}"), devices[0].address);
+    assert_eq!("SNSW-001P16EU", devices[0].model);
+    assert_eq!(2, devices[0].generation);
+    assert_eq!("1.0.8", devices[0].firmware);
+}
+
+#[tokio::test]
+async fn scan_local_network() {
+    // arrange
+    let mock_body = serde_json::json!({
+      "id": 1,
+      "src": "shellyplus1pm-a8032abe54dc",
+      "result": {
+        "name": null,
+        "id": "shellyplus1pm-a8032abe54dc",
+        "mac": "A8032ABE54DC",
+        "model": "SNSW-001P16EU",
+        "gen": 2,
+        "fw_id": "20231107-164738/1.0.8-g8c7bb8d",
+        "ver": "1.0.8",
+        "app": "Plus1PM",
+        "auth_en": false,
+        "auth_domain": null
+      }
+    });
+
+    let mut server = Server::new_async().await;
+    let port: u16 = server
+        .host_with_port()
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok())
+        .expect("Unexpected");
+    let mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Shelly.GetDeviceInfo"}"#)
+        .with_body(mock_body.to_string())
+        .create_async()
+        .await;
+
+    // act
+    let devices = discovery::scan(
+        "127.0.0.0/30".parse().expect("Unexpected"),
+        port,
+        std::time::Duration::from_millis(500),
+    )
+    .await;
+
+    // assert
+    mock.assert_async().await;
+    assert_eq!(1, devices.len());
+    assert_eq!(format!("127.0.0.1:{port}"), devices[0].address);
+    assert_eq!("A8032ABE54DC", devices[0].mac);
+}
+
+#[tokio::test]
+async fn scan_gen1_device() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let port: u16 = server
+        .host_with_port()
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok())
+        .expect("Unexpected");
+    let get_device_info_mock = server
+        .mock("POST", "/rpc")
+        .with_status(404)
+        .create_async()
+        .await;
+    let get_shelly_mock = server
+        .mock("GET", "/shelly")
+        .with_body(data::mockito::gen1::get_shelly())
+        .create_async()
+        .await;
+
+    // act
+    let devices = discovery::scan(
+        "127.0.0.1/32".parse().expect("Unexpected"),
+        port,
+        std::time::Duration::from_millis(500),
+    )
+    .await;
+
+    // assert
+    get_device_info_mock.assert_async().await;
+    get_shelly_mock.assert_async().await;
+    assert_eq!(1, devices.len());
+    assert_eq!(1, devices[0].generation);
+    assert_eq!("SHSW-1", devices[0].model);
+    assert_eq!("98CDAC1F03A8", devices[0].mac);
+}
+
+const ALL_METHODS: [&str; 7] = [
+    "Shelly.GetComponents",
+    "Schedule.Create",
+    "Schedule.Update",
+    "Schedule.List",
+    "KVS.Get",
+    "KVS.Set",
+    "KVS.Delete",
+];
+
+#[tokio::test]
+async fn check_device_supported() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_device_info_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_DEVICE_INFO)
+        .with_body(data::mockito::with_body::get_device_info(2, Some("switch")))
+        .create_async()
+        .await;
+    let list_methods_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::LIST_METHODS)
+        .with_body(data::mockito::with_body::list_methods(&ALL_METHODS))
+        .create_async()
+        .await;
+    let get_components_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_COMPONENTS)
+        .with_body(data::mockito::with_body::get_components(&[
+            "sys", "switch:0", "switch:1",
+        ]))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_switch_id(1);
+
+    // act
+    let result = core.check_device().await;
+
+    // assert
+    get_device_info_mock.assert_async().await;
+    list_methods_mock.assert_async().await;
+    get_components_mock.assert_async().await;
+    assert_eq!("SNSW-102P16EU", result.expect("Unexpected").model);
+}
+
+#[tokio::test]
+async fn check_device_cover_profile() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_device_info_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_DEVICE_INFO)
+        .with_body(data::mockito::with_body::get_device_info(2, Some("cover")))
+        .create_async()
+        .await;
+    let list_methods_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::LIST_METHODS)
+        .with_body(data::mockito::with_body::list_methods(&ALL_METHODS))
+        .create_async()
+        .await;
+    let get_components_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_COMPONENTS)
+        .with_body(data::mockito::with_body::get_components(&[
+            "sys", "cover:0",
+        ]))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let result = core.check_device().await;
+
+    // assert
+    get_device_info_mock.assert_async().await;
+    list_methods_mock.assert_async().await;
+    get_components_mock.assert_async().await;
+    let e = result.expect_err("Expected Error is Ok");
+    match e.downcast_ref::<CustomError>() {
+        Some(CustomError::UnsupportedDevice(msg)) => {
+            assert!(msg.contains("profile 'cover' has no switch:0"), "{msg}")
+        }
+        _ => panic!("Unexpected error {e}"),
+    }
+}
+
+#[tokio::test]
+async fn check_device_without_kvs() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_device_info_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_DEVICE_INFO)
+        .with_body(data::mockito::with_body::get_device_info(2, None))
+        .create_async()
+        .await;
+    let list_methods_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::LIST_METHODS)
+        .with_body(data::mockito::with_body::list_methods(&ALL_METHODS[..4]))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let result = core.check_device().await;
+
+    // assert
+    get_device_info_mock.assert_async().await;
+    list_methods_mock.assert_async().await;
+    let e = result.expect_err("Expected Error is Ok");
+    assert!(
+        matches!(
+            e.downcast_ref::<CustomError>(),
+            Some(CustomError::UnsupportedDevice(_))
+        ),
+        "Unexpected error {e}"
+    );
+}
+
+#[tokio::test]
+async fn check_device_gen1() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let rpc_mock = server
+        .mock("POST", "/rpc")
+        .with_status(404)
+        .with_body("Not Found")
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let result = core.check_device().await;
+
+    // assert
+    rpc_mock.assert_async().await;
+    let e = result.expect_err("Expected Error is Ok");
+    assert!(
+        matches!(
+            e.downcast_ref::<CustomError>(),
+            Some(CustomError::UnsupportedDevice(_))
+        ),
+        "Unexpected error {e}"
+    );
+}
+
+#[tokio::test]
+async fn gen1_writes_schedule_rules() {
+    // arrange
+    let lat = 52.516293;
+    let lon = 13.377713;
+    // Wednesday, 20 December 2023 16:20:00
+    let unix_timestamp = 1703085600;
+    // Wednesday, 20 December 2023 08:14:19
+    let sunrise = 1703056459;
+    // Wednesday, 20 December 2023 15:53:24
+    let sunset = 1703084004;
+    let light_on = sunset - 12 * 60 * 60;
+    let rules = [
+        data::mockito::gen1::schedule_rule(light_on, "on"),
+        data::mockito::gen1::schedule_rule(sunrise, "off"),
+    ];
+    let stale_rules = [
+        data::mockito::gen1::schedule_rule(light_on + 7 * 60, "on"),
+        data::mockito::gen1::schedule_rule(sunrise, "off"),
+    ];
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_settings_mock = server
+        .mock("GET", "/settings")
+        .with_body(data::mockito::gen1::get_settings(lat, lon))
+        .create_async()
+        .await;
+    let get_status_mock = server
+        .mock("GET", "/status")
+        .with_body(data::mockito::gen1::get_status("16:20", unix_timestamp))
+        .create_async()
+        .await;
+    let get_relay_settings_mock = server
+        .mock("GET", "/settings/relay/0")
+        .match_query(Matcher::Missing)
+        .with_body(data::mockito::gen1::relay_settings(
+            true,
+            &stale_rules.each_ref().map(String::as_str),
+        ))
+        .create_async()
+        .await;
+    let set_schedule_rules_mock = server
+        .mock("GET", "/settings/relay/0")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("schedule".into(), "true".into()),
+            Matcher::UrlEncoded("schedule_rules".into(), rules.join(",")),
+        ]))
+        .with_body(data::mockito::gen1::relay_settings(
+            true,
+            &rules.each_ref().map(String::as_str),
+        ))
+        .create_async()
+        .await;
+
+    let client = Gen1DeviceClient::new(&host);
+    let core = Gen1Controller::new(&client);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(12)).await;
+
+    // assert
+    get_settings_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_relay_settings_mock.assert_async().await;
+    set_schedule_rules_mock.assert_async().await;
+    assert_eq!(vec![(light_on, sunrise)], actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn gen1_schedule_rules_up_to_date() {
+    // arrange
+    // Wednesday, 20 December 2023 03:53:24 (12h before sunset) to 08:14:19 (sunrise)
+    let rules = [
+        data::mockito::gen1::schedule_rule(1703084004 - 12 * 60 * 60, "on"),
+        data::mockito::gen1::schedule_rule(1703056459, "off"),
+    ];
+
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_settings_mock = server
+        .mock("GET", "/settings")
+        .with_body(data::mockito::gen1::get_settings(52.516293, 13.377713))
+        .create_async()
+        .await;
+    let get_status_mock = server
+        .mock("GET", "/status")
+        .with_body(data::mockito::gen1::get_status("16:20", 1703085600))
+        .create_async()
+        .await;
+    let get_relay_settings_mock = server
+        .mock("GET", "/settings/relay/0")
+        .match_query(Matcher::Missing)
+        .with_body(data::mockito::gen1::relay_settings(
+            true,
+            &rules.each_ref().map(String::as_str),
+        ))
+        .create_async()
+        .await;
+    let set_schedule_rules_mock = server
+        .mock("GET", "/settings/relay/0")
+        .match_query(Matcher::Any)
+        .expect(0)
+        .create_async()
+        .await;
+
+    let client = Gen1DeviceClient::new(&host);
+    let core = Gen1Controller::new(&client);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(12)).await;
+
+    // assert
+    get_settings_mock.assert_async().await;
+    get_status_mock.assert_async().await;
+    get_relay_settings_mock.assert_async().await;
+    set_schedule_rules_mock.assert_async().await;
+    assert!(actual.is_ok(), "Expected Ok is Error");
+}
+
+#[tokio::test]
+async fn gen1_check_device() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let get_shelly_mock = server
+        .mock("GET", "/shelly")
+        .with_body(data::mockito::gen1::get_shelly())
+        .expect(2)
+        .create_async()
+        .await;
+    let get_relay_status_mock = server
+        .mock("GET", "/relay/0")
+        .with_body(data::mockito::gen1::get_relay_status(false))
+        .create_async()
+        .await;
+    let get_missing_relay_mock = server
+        .mock("GET", "/relay/1")
+        .with_status(404)
+        .with_body("Not Found")
+        .create_async()
+        .await;
+
+    let client = Gen1DeviceClient::new(&host);
+
+    // act
+    let relay_0 = Gen1Controller::new(&client).check_device().await;
+    let relay_1 = Gen1Controller::new(&client)
+        .with_switch_id(1)
+        .check_device()
+        .await;
+
+    // assert
+    get_shelly_mock.assert_async().await;
+    get_relay_status_mock.assert_async().await;
+    get_missing_relay_mock.assert_async().await;
+    assert_eq!(
+        Some("SHSW-1".to_string()),
+        relay_0.expect("Unexpected").model
+    );
+    let e = relay_1.expect_err("Expected Error is Ok");
+    assert!(
+        matches!(
+            e.downcast_ref::<CustomError>(),
+            Some(CustomError::UnsupportedDevice(_))
+        ),
+        "Unexpected error {e}"
+    );
+}
+
+#[tokio::test]
+async fn deploy_script_replaces_previous() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let list_scripts_mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Script.List"}"#)
+        .with_body(data::mockito::with_body::list_scripts(&[
+            (1, "dle-default-0", true),
+            (2, "button", true),
+        ]))
+        .create_async()
+        .await;
+    let stop_script_mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Script.Stop","params":{"id":1}}"#)
+        .with_body(data::mockito::with_body::start_stop_script(true))
+        .create_async()
+        .await;
+    let delete_script_mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Script.Delete","params":{"id":1}}"#)
+        .with_body(data::mockito::with_body::null_result())
+        .create_async()
+        .await;
+
+    // the schedule jobs of the instance are disabled, there are none
+    let mut get_value_mocks = Vec::new();
+    for key in [
+        KeyNamespace::default().job_key(0),
+        SCHEDULE_JOB_ID.to_string(),
+        KeyNamespace::default().off_job_key(0),
+        KeyNamespace::default().evening_job_key(0),
+        KeyNamespace::default().evening_off_job_key(0),
+    ] {
+        get_value_mocks.push(
+            server
+                .mock("POST", "/rpc")
+                .match_body(data::mockito::match_body::get_value(&key).as_str())
+                .with_body(data::mockito::with_body::get_value_error(&key))
+                .create_async()
+                .await,
+        );
+    }
+    let list_schedule_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::LIST_SCHEDULE)
+        .with_body(data::mockito::with_body::list_schedule_empty(5))
+        .create_async()
+        .await;
+
+    let create_script_mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Script.Create","params":{"name":"dle-default-0"}}"#)
+        .with_body(data::mockito::with_body::create_script(3))
+        .create_async()
+        .await;
+    let put_first_chunk_mock = server
+        .mock("POST", "/rpc")
+        .match_body(Matcher::AllOf(vec![
+            Matcher::PartialJson(serde_json::json!({
+                "method": "Script.PutCode",
+                "params": { "id": 3, "append": false }
+            })),
+            Matcher::Regex(r#""code":"let CONFIG = \{"#.to_string()),
+        ]))
+        .with_body(data::mockito::with_body::put_script_code(1024))
+        .create_async()
+        .await;
+    let put_chunk_mock = server
+        .mock("POST", "/rpc")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "method": "Script.PutCode",
+            "params": { "id": 3, "append": true }
+        })))
+        .with_body(data::mockito::with_body::put_script_code(2048))
+        .expect_at_least(1)
+        .create_async()
+        .await;
+    let set_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(
+            r#"{"id":1,"method":"Script.SetConfig","params":{"config":{"enable":true},"id":3}}"#,
+        )
+        .with_body(data::mockito::with_body::null_result())
+        .create_async()
+        .await;
+    let start_script_mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Script.Start","params":{"id":3}}"#)
+        .with_body(data::mockito::with_body::start_stop_script(false))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let actual = core.deploy_script(&DayLength::Hours(12)).await;
+
+    // assert
+    list_scripts_mock.assert_async().await;
+    stop_script_mock.assert_async().await;
+    delete_script_mock.assert_async().await;
+    for mock in get_value_mocks {
+        mock.assert_async().await;
+    }
+    list_schedule_mock.assert_async().await;
+    create_script_mock.assert_async().await;
+    put_first_chunk_mock.assert_async().await;
+    put_chunk_mock.assert_async().await;
+    set_config_mock.assert_async().await;
+    start_script_mock.assert_async().await;
+    assert_eq!(3, actual.expect("Unexpected"));
+}
+
+#[tokio::test]
+async fn execute_refuses_deployed_script() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let list_scripts_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::LIST_SCRIPTS)
+        .with_body(data::mockito::with_body::list_scripts(&[(
+            1,
+            "dle-default-0",
+            true,
+        )]))
+        .create_async()
+        .await;
+    let get_config_mock = server
+        .mock("POST", "/rpc")
+        .match_body(data::mockito::match_body::GET_CONFIG)
+        .expect(0)
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client);
+
+    // act
+    let actual = core.execute(&DayLength::Hours(12)).await;
+
+    // assert
+    list_scripts_mock.assert_async().await;
+    get_config_mock.assert_async().await;
+    let error = actual.expect_err("Expected Error is Ok");
+    assert!(matches!(
+        error.downcast_ref::<CustomError>(),
+        Some(CustomError::ScriptDeployed(_))
+    ));
+}
+
+#[tokio::test]
+async fn remove_script_without_script() {
+    // arrange
+    let mut server = Server::new_async().await;
+    let host = server.host_with_port();
+    let list_scripts_mock = server
+        .mock("POST", "/rpc")
+        .match_body(r#"{"id":1,"method":"Script.List"}"#)
+        .with_body(data::mockito::with_body::list_scripts(&[(
+            2, "button", true,
+        )]))
+        .create_async()
+        .await;
+
+    let client = Gen2DeviceClient::new(&host);
+    let core = Controller::new(&client).with_switch_id(1);
+
+    // act
+    let actual = core.remove_script().await;
+
+    // assert
+    list_scripts_mock.assert_async().await;
+    assert!(!actual.expect("Unexpected"));
+}